@@ -0,0 +1,150 @@
+use std::rc::Rc;
+
+use crate::builtin::{Argument, Builtin};
+use crate::error::Span;
+
+/// A whole script, or whatever is typed into the REPL at once
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+/// An expression at the top level of a program
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub expr: Expr,
+
+    /// Whether the summary of the value is printed,
+    /// which is the case unless the statement is terminated by a semicolon
+    pub print: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+
+    /// `!x`, which is 1 when `x` is zero and 0 otherwise
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+
+    /// The comparisons, which give 1 when they hold and 0 otherwise
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+
+    /// `&&` and `||`, which give 1 or 0 and do not evaluate the right-hand side when the left-hand side decides
+    And,
+    Or,
+}
+
+/// A function defined by `@fn name($a, $b) { ... }`
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Expr>,
+
+    /// The source text containing the definition, into which the spans in `body` point
+    pub source: Rc<str>,
+}
+
+/// An argument of a built-in function as written
+#[derive(Clone, Debug)]
+pub enum BuiltinArgument {
+    /// An argument of the kind [`ArgumentKind::Expression`](crate::builtin::ArgumentKind::Expression),
+    /// evaluated at each call
+    Expression(Expr),
+
+    /// An argument of any other kind, which is passed as is
+    Other(Argument),
+}
+
+#[derive(Clone, Debug)]
+pub enum ExprKind {
+    /// The raw text of a numeric literal, read in the radix context at the time of evaluation
+    Literal(String),
+
+    /// A literal without a prefix such as `1e-5`, whose sign is at the offset `sign`:
+    /// a single literal where `e` is not a digit, and the sum or difference `(1e - 5)` in radix 15 to 36,
+    /// as the radix context at the time of evaluation decides
+    SignedExponent {
+        literal: String,
+        sign: usize,
+    },
+
+    /// `$_`
+    PreviousValue,
+
+    /// `$name`
+    Variable(String),
+
+    /// `$name = expr`
+    Assign(String, Box<Expr>),
+
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+
+    /// `{ expr; expr; ... }`, whose value is that of the last expression
+    Block(Vec<Expr>),
+
+    /// `@hexadecimal { ... }` and the like
+    RadixBlock(u32, Vec<Expr>),
+
+    /// `[a_0; a_1, ..., a_n]`, optionally ending with `@rep{...}`
+    ContinuedFraction {
+        terms: Vec<Expr>,
+        period: Option<Vec<Expr>>,
+    },
+
+    /// `@if (condition) { ... } @else { ... }`, where `@else @if ...` is an `else_branch` holding just the inner `@if`
+    If {
+        condition: Box<Expr>,
+        then_branch: Vec<Expr>,
+        else_branch: Option<Vec<Expr>>,
+    },
+
+    /// `@repeat(count) { ... }`
+    Repeat {
+        count: Box<Expr>,
+        body: Vec<Expr>,
+    },
+
+    /// `@while (condition) { ... }`
+    While {
+        condition: Box<Expr>,
+        body: Vec<Expr>,
+    },
+
+    /// `@sqrt(x)`, `@assert_eq(left, right)` and the other built-in functions, with the arguments already checked against their kinds
+    Builtin {
+        builtin: Builtin,
+        args: Vec<BuiltinArgument>,
+    },
+
+    /// `@fn name(...) { ... }`, which makes the function available to the rest of the file
+    DefineFunction(Rc<Function>),
+
+    /// `@name(arg, ...)`, a call of a function defined by `@fn`
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
+}
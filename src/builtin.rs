@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use num_rational::BigRational;
+
+use crate::ast::Expr;
+use crate::error::{Error, ErrorKind, Span};
+use crate::interpreter::Interpreter;
+use crate::number_theory::expansion_lengths;
+use crate::quadratic;
+use crate::value::Value;
+
+/// What a built-in function expects as each argument, which decides how the argument is parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgumentKind {
+    /// Any expression, evaluated before the built-in is called
+    Expression,
+
+    /// A string literal such as `"file.periodicode"`
+    String,
+
+    /// `{ "a.periodicode"; "b.periodicode" }`, one or more string literals each kept with its place in the source
+    Strings,
+
+    /// A radix name such as `@hexadecimal`, or `@base N` or `@radix(N)` with `N` in decimal
+    Radix,
+
+    /// `{ ...; ... }`, or a string holding such code, which is parsed only when evaluated so that it can contain a syntax error.
+    /// The built-in decides whether and how many times to evaluate it with [`Interpreter::eval_block_argument`].
+    Block,
+
+    /// A kind of error such as `@division_by_zero`, or a string that the error message must contain
+    ExpectedFailure,
+}
+
+impl ArgumentKind {
+    /// How the argument is described in error messages
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            ArgumentKind::Expression => "an expression",
+            ArgumentKind::String => "a string",
+            ArgumentKind::Strings => "a block of strings `{ \"...\"; ... }`",
+            ArgumentKind::Radix => "a radix such as `@hexadecimal` or `@base 36`",
+            ArgumentKind::Block => "a block `{ ... }` or a string of code",
+            ArgumentKind::ExpectedFailure => {
+                "a string or a kind of error such as `@division_by_zero`"
+            }
+        }
+    }
+}
+
+/// The code given as an argument of the kind [`ArgumentKind::Block`]
+#[derive(Clone, Debug)]
+pub struct Block(pub(crate) BlockBody);
+
+#[derive(Clone, Debug)]
+pub(crate) enum BlockBody {
+    /// `{ ...; ... }`
+    Parsed(Rc<[Expr]>),
+
+    /// `"..."`, which is parsed only when evaluated
+    Source(Rc<str>),
+}
+
+/// What an error must match, as given by the second argument of `@should_fail`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpectedFailure {
+    /// `"..."`, a part of the error message
+    Message(String),
+
+    /// `@division_by_zero` and the like
+    Kind(ErrorKind),
+}
+
+/// An argument passed to a built-in function, in the kind declared for its position
+#[derive(Clone, Debug)]
+pub enum Argument {
+    Value(Value),
+    String(String),
+    Strings(Vec<(String, Span)>),
+    Radix(u32),
+    Block(Block),
+    ExpectedFailure(ExpectedFailure),
+}
+
+impl Argument {
+    fn into_value(self) -> Value {
+        match self {
+            Argument::Value(value) => value,
+            _ => unreachable!("the parser checks the kind of each argument"),
+        }
+    }
+}
+
+/// The implementation of a built-in function, which receives one argument for each parameter that was given
+pub type BuiltinFunction = dyn Fn(&mut Interpreter, Vec<Argument>) -> Result<Value, Error>;
+
+/// A built-in function called as `@name(arg, ...)`, together with the kinds of its arguments
+#[derive(Clone)]
+pub struct Builtin {
+    name: String,
+    params: Vec<ArgumentKind>,
+
+    /// How many of the leading parameters must be given; the others can be omitted
+    required: usize,
+    function: Rc<BuiltinFunction>,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builtin")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("required", &self.required)
+            .finish_non_exhaustive()
+    }
+}
+
+/// `one`, `two` and the like
+fn number_in_words(count: usize) -> String {
+    match count {
+        0 => "no".to_owned(),
+        1 => "one".to_owned(),
+        2 => "two".to_owned(),
+        3 => "three".to_owned(),
+        _ => count.to_string(),
+    }
+}
+
+impl Builtin {
+    /// A built-in taking one argument of each kind in `params`, all of which must be given
+    pub fn new(
+        name: impl Into<String>,
+        params: &[ArgumentKind],
+        function: impl Fn(&mut Interpreter, Vec<Argument>) -> Result<Value, Error> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            params: params.to_vec(),
+            required: params.len(),
+            function: Rc::new(function),
+        }
+    }
+
+    /// Lets the last `count` arguments be omitted, in which case the function receives fewer arguments
+    #[must_use]
+    pub fn with_optional(mut self, count: usize) -> Self {
+        self.required = self.params.len().saturating_sub(count);
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn params(&self) -> &[ArgumentKind] {
+        &self.params
+    }
+
+    /// The number of arguments that must be given
+    #[must_use]
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    /// The message for a call with too many or too few arguments
+    pub(crate) fn arity_message(&self) -> String {
+        let (required, total) = (self.required, self.params.len());
+        let expected = if required == total {
+            let plural = if total == 1 { "" } else { "s" };
+            format!("exactly {} argument{plural}", number_in_words(total))
+        } else if required == 0 {
+            format!("at most {} arguments", number_in_words(total))
+        } else {
+            let conjunction = if total == required + 1 { "or" } else { "to" };
+            format!(
+                "{} {conjunction} {} arguments",
+                number_in_words(required),
+                number_in_words(total)
+            )
+        };
+        format!("The built-in function `@{}` expects {expected}", self.name)
+    }
+
+    pub(crate) fn call(&self, ctx: &mut Interpreter, args: Vec<Argument>) -> Result<Value, Error> {
+        (self.function)(ctx, args)
+    }
+}
+
+/// The assertions such as `@assert_eq`, which count the passed ones in the test report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Assertion {
+    /// `@assert(value)`, which holds unless the value is zero
+    NonZero,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+
+    /// `@assert_approx(left, right, tolerance)`
+    Approx,
+}
+
+impl Assertion {
+    const ALL: [Assertion; 8] = [
+        Assertion::NonZero,
+        Assertion::Eq,
+        Assertion::Ne,
+        Assertion::Lt,
+        Assertion::Le,
+        Assertion::Gt,
+        Assertion::Ge,
+        Assertion::Approx,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Assertion::NonZero => "assert",
+            Assertion::Eq => "assert_eq",
+            Assertion::Ne => "assert_ne",
+            Assertion::Lt => "assert_lt",
+            Assertion::Le => "assert_le",
+            Assertion::Gt => "assert_gt",
+            Assertion::Ge => "assert_ge",
+            Assertion::Approx => "assert_approx",
+        }
+    }
+
+    /// The names of the arguments, as shown when the assertion fails
+    pub(crate) fn operand_names(self) -> &'static [&'static str] {
+        match self {
+            Assertion::NonZero => &["value"],
+            Assertion::Approx => &["left", "right", "tolerance"],
+            _ => &["left", "right"],
+        }
+    }
+
+    /// The condition that must hold, in terms of `operand_names`
+    pub(crate) fn condition(self) -> &'static str {
+        match self {
+            Assertion::NonZero => "value != 0",
+            Assertion::Eq => "left == right",
+            Assertion::Ne => "left != right",
+            Assertion::Lt => "left < right",
+            Assertion::Le => "left <= right",
+            Assertion::Gt => "left > right",
+            Assertion::Ge => "left >= right",
+            Assertion::Approx => "|left - right| <= tolerance",
+        }
+    }
+}
+
+/// The built-in functions known to an interpreter, by name
+#[derive(Clone)]
+pub(crate) struct Builtins(HashMap<String, Builtin>);
+
+impl Builtins {
+    pub(crate) fn get(&self, name: &str) -> Option<&Builtin> {
+        self.0.get(name)
+    }
+
+    /// Adds `builtin`, replacing any built-in of the same name
+    pub(crate) fn insert(&mut self, builtin: Builtin) {
+        self.0.insert(builtin.name.clone(), builtin);
+    }
+}
+
+impl Default for Builtins {
+    /// The built-in functions of the language
+    fn default() -> Self {
+        use ArgumentKind::{Block, Expression, Radix, Strings};
+
+        let mut builtins = Builtins(HashMap::new());
+        builtins.insert(Builtin::new("sqrt", &[Expression], |_, args| {
+            match args.into_iter().next().map(Argument::into_value) {
+                // exact when the argument is the square of a rational
+                Some(Value::Rational(r)) => quadratic::sqrt(&r),
+                Some(Value::Quadratic(q)) => Err(Error::new(
+                    ErrorKind::Arithmetic,
+                    format!("The square root of a quadratic irrational {q} is not supported"),
+                )),
+                None => unreachable!("the parser checks the number of arguments"),
+            }
+        }));
+        // `@load` starts each file afresh, while `@load_dirty` shares the variables and the radix context with it
+        for (name, dirty) in [("load", false), ("load_dirty", true)] {
+            builtins.insert(Builtin::new(name, &[Strings], move |ctx, args| {
+                let [Argument::Strings(files)] = &args[..] else {
+                    unreachable!("the parser checks the kind of each argument")
+                };
+                ctx.load_files(files, dirty)
+            }));
+        }
+        for (name, is_period) in [("preperiod", false), ("period", true)] {
+            builtins.insert(
+                Builtin::new(name, &[Expression, Radix], move |ctx, args| {
+                    let mut args = args.into_iter();
+                    let value = args.next().map(Argument::into_value);
+                    let radix = match args.next() {
+                        Some(Argument::Radix(radix)) => radix,
+                        _ => ctx.radix_context,
+                    };
+                    match value {
+                        Some(Value::Rational(r)) => {
+                            let (preperiod, period) = expansion_lengths(r.denom(), radix);
+                            let length = if is_period { period } else { preperiod.into() };
+                            Ok(Value::Rational(BigRational::from_integer(length)))
+                        }
+                        Some(Value::Quadratic(q)) => Err(Error::new(
+                            ErrorKind::Arithmetic,
+                            format!("The digits of a quadratic irrational {q} never repeat"),
+                        )),
+                        None => unreachable!("the parser checks the number of arguments"),
+                    }
+                })
+                .with_optional(1),
+            );
+        }
+        builtins.insert(Builtin::new("set_radix", &[Radix], |ctx, args| {
+            let [Argument::Radix(radix)] = args[..] else {
+                unreachable!("the parser checks the kind of each argument")
+            };
+            ctx.radix_context = radix;
+            Ok(Value::Rational(BigRational::from_integer(radix.into())))
+        }));
+        builtins.insert(
+            Builtin::new(
+                "should_fail",
+                &[Block, ArgumentKind::ExpectedFailure],
+                |ctx, args| {
+                    let mut args = args.into_iter();
+                    let Some(Argument::Block(body)) = args.next() else {
+                        unreachable!("the parser checks the kind of each argument")
+                    };
+                    let expected = args.next().map(|arg| match arg {
+                        Argument::ExpectedFailure(expected) => expected,
+                        _ => unreachable!("the parser checks the kind of each argument"),
+                    });
+                    ctx.eval_should_fail(&body, expected.as_ref())
+                },
+            )
+            .with_optional(1),
+        );
+        for assertion in Assertion::ALL {
+            let params = vec![Expression; assertion.operand_names().len()];
+            builtins.insert(Builtin::new(assertion.name(), &params, move |ctx, args| {
+                ctx.eval_assertion(
+                    assertion,
+                    args.into_iter().map(Argument::into_value).collect(),
+                )
+            }));
+        }
+        builtins
+    }
+}
@@ -0,0 +1,179 @@
+use big_s::S;
+use periodicode::{
+    radix_from_name, OutputFormat, Verbosity, DEFAULT_ITERATION_LIMIT, DEFAULT_MAX_PERIOD_DIGITS,
+};
+
+pub const USAGE: &str = "\
+Usage: periodicode [OPTIONS] [FILE]...
+       periodicode test [OPTIONS] <PATH>...
+
+Runs each FILE as if it were loaded with `@load`.
+With neither FILE nor `-e`, an interactive session is started if the standard input is a terminal;
+otherwise, the script is read from the standard input.
+
+`periodicode test` runs each file, and each `.periodicode` file inside each directory, as a test suite:
+a failed assertion (`@assert`, `@assert_eq`, `@assert_ne`, `@assert_lt`, `@assert_le`, `@assert_gt`, `@assert_ge`,
+`@assert_approx` or `@should_fail`) is reported, and the execution resumes from the next statement.
+The exit status is non-zero when any assertion failed or any file could not be executed to the end.
+
+Options:
+  -e, --eval <CODE>     Execute CODE (can be given multiple times)
+  -r, --radix <RADIX>   Start in the radix context RADIX (a name such as `hexadecimal`, or a number of at least 2)
+  -                     Read a script from the standard input
+  -i, --interactive     Start an interactive session after executing everything else
+      --max-iterations <N>
+                        Stop `@repeat` and `@while` with an error after N iterations (default: 100000)
+      --max-period-digits <N>
+                        Show a period of more than N repeating digits as its first and last digits and its length (default: 100)
+  -q, --quiet           Print only the results, without echoing the input lines
+      --json            Print each result as a JSON object on its own line, and nothing else
+      --color           Always color the output
+      --no-color        Never color the output (also when the environment variable NO_COLOR is set)
+  -h, --help            Print this help";
+
+/// A single piece of code to be executed, in the order given on the command line
+#[derive(Debug, PartialEq, Eq)]
+pub enum Source {
+    File(String),
+    Eval(String),
+    Stdin,
+}
+
+/// Whether the output is colored with ANSI escape sequences
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color the output written to a stream, given whether that stream is a terminal
+    pub fn colored(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Options {
+    pub radix_context: u32,
+    pub sources: Vec<Source>,
+    pub interactive: bool,
+
+    /// Whether the files are run as test suites by `periodicode test`
+    pub test: bool,
+    pub iteration_limit: u64,
+    pub max_period_digits: usize,
+    pub verbosity: Verbosity,
+    pub output_format: OutputFormat,
+    pub color: ColorChoice,
+    pub help: bool,
+}
+
+fn parse_radix(arg: &str) -> Result<u32, String> {
+    if let Some(radix) = radix_from_name(arg) {
+        return Ok(radix);
+    }
+    match arg.parse::<u32>() {
+        Ok(radix) if radix >= 2 => Ok(radix),
+        _ => Err(format!(
+            "invalid radix `{arg}`: expected a radix name or a number of at least 2"
+        )),
+    }
+}
+
+impl Options {
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options {
+            radix_context: 10,
+            sources: vec![],
+            interactive: false,
+            test: false,
+            iteration_limit: DEFAULT_ITERATION_LIMIT,
+            max_period_digits: DEFAULT_MAX_PERIOD_DIGITS,
+            verbosity: Verbosity::Normal,
+            output_format: OutputFormat::Summary,
+            color: ColorChoice::Auto,
+            help: false,
+        };
+
+        let mut args = args.into_iter().peekable();
+        if args.peek().is_some_and(|arg| arg == "test") {
+            args.next();
+            options.test = true;
+        }
+
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "-h" | "--help" => options.help = true,
+                "-i" | "--interactive" => options.interactive = true,
+                "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+                "--json" => options.output_format = OutputFormat::JsonLines,
+                "--color" => options.color = ColorChoice::Always,
+                "--no-color" => options.color = ColorChoice::Never,
+                "-e" | "--eval" => {
+                    let code = args
+                        .next()
+                        .ok_or_else(|| format!("`{arg}` expects an argument"))?;
+                    options.sources.push(Source::Eval(code));
+                }
+                "-r" | "--radix" => {
+                    let radix = args
+                        .next()
+                        .ok_or_else(|| format!("`{arg}` expects an argument"))?;
+                    options.radix_context = parse_radix(&radix)?;
+                }
+                "--max-iterations" => {
+                    let limit = args
+                        .next()
+                        .ok_or_else(|| format!("`{arg}` expects an argument"))?;
+                    options.iteration_limit = limit.parse().map_err(|_| {
+                        format!(
+                            "invalid iteration limit `{limit}`: expected a non-negative integer"
+                        )
+                    })?;
+                }
+                "--max-period-digits" => {
+                    let limit = args
+                        .next()
+                        .ok_or_else(|| format!("`{arg}` expects an argument"))?;
+                    options.max_period_digits = limit.parse().map_err(|_| {
+                        format!(
+                            "invalid number of period digits `{limit}`: expected a non-negative integer"
+                        )
+                    })?;
+                }
+                "-" => options.sources.push(Source::Stdin),
+                "--" => options.sources.extend(args.by_ref().map(Source::File)),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ => options.sources.push(Source::File(arg)),
+            }
+        }
+
+        if options.test && !options.help {
+            if options.interactive {
+                return Err(S("`test` cannot be combined with `-i`"));
+            }
+            if options.sources.is_empty() {
+                return Err(S("`test` expects at least one file or directory"));
+            }
+            if options
+                .sources
+                .iter()
+                .any(|source| !matches!(source, Source::File(_)))
+            {
+                return Err(S("`test` only accepts files and directories"));
+            }
+        }
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::Function;
+use crate::value::Value;
+
+/// A function defined by `@fn`, together with the name of the file that defined it
+#[derive(Clone)]
+pub struct UserFunction {
+    pub definition: Rc<Function>,
+    pub source_name: String,
+}
+
+/// The named variables (`$name`) and the functions defined by `@fn` visible to an interpreter.
+///
+/// `@load` starts with an empty environment, whereas `@load_dirty` shares the environment of the loader.
+/// Blocks, decorated or not, do not introduce a new environment,
+/// but each function call has its own variables on top of those of the file.
+#[derive(Clone, Default)]
+pub struct Environment {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, UserFunction>,
+
+    /// The parameters and the variables assigned in each function call being evaluated, innermost last
+    frames: Vec<HashMap<String, Value>>,
+}
+
+impl Environment {
+    /// Looks in the innermost function call first, then in the variables of the file
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.frames
+            .last()
+            .and_then(|frame| frame.get(name))
+            .or_else(|| self.variables.get(name))
+    }
+
+    /// Assigns to a variable local to the innermost function call, if any
+    pub fn assign(&mut self, name: String, value: Value) {
+        self.frames
+            .last_mut()
+            .unwrap_or(&mut self.variables)
+            .insert(name, value);
+    }
+
+    pub fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    /// Defines a function, replacing any earlier one of the same name
+    pub fn define_function(&mut self, function: UserFunction) {
+        self.functions
+            .insert(function.definition.name.clone(), function);
+    }
+
+    pub fn push_frame(&mut self, arguments: HashMap<String, Value>) {
+        self.frames.push(arguments);
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+}
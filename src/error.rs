@@ -0,0 +1,312 @@
+use std::fmt;
+
+/// What went wrong, independently of the wording of the message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The code does not follow the grammar, e.g. a mismatched parenthesis or a malformed numeric literal
+    Syntax,
+    /// `@name` where `name` is neither a built-in function nor a radix name
+    UnknownIdentifier,
+    UndefinedVariable,
+    DivisionByZero,
+    /// The operation has no exact result, e.g. `2^(1/2)` or `@sqrt(2) + @sqrt(3)`
+    Arithmetic,
+    /// An assertion such as `@assert`, `@assert_eq`, `@assert_lt` or `@assert_approx` that does not hold
+    Assertion,
+    /// A `@should_fail` whose code succeeded
+    DidNotFail,
+    /// A `@should_fail` whose code failed with an error other than the expected one
+    UnexpectedFailure,
+    /// A loop ran for more iterations than allowed
+    IterationLimit,
+    /// Function calls were nested too deeply, usually because of a recursion that does not terminate
+    RecursionLimit,
+    /// A file could not be read
+    Io,
+}
+
+impl ErrorKind {
+    /// Whether the error is a failed assertion rather than a problem with the code
+    #[must_use]
+    pub fn is_assertion_failure(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Assertion | ErrorKind::DidNotFail | ErrorKind::UnexpectedFailure
+        )
+    }
+
+    /// The kind called `name`, as in `@should_fail("1/0", @division_by_zero)`
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ErrorKind::Syntax,
+            ErrorKind::UnknownIdentifier,
+            ErrorKind::UndefinedVariable,
+            ErrorKind::DivisionByZero,
+            ErrorKind::Arithmetic,
+            ErrorKind::Assertion,
+            ErrorKind::DidNotFail,
+            ErrorKind::UnexpectedFailure,
+            ErrorKind::IterationLimit,
+            ErrorKind::RecursionLimit,
+            ErrorKind::Io,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == name)
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::UnknownIdentifier => "unknown_identifier",
+            ErrorKind::UndefinedVariable => "undefined_variable",
+            ErrorKind::DivisionByZero => "division_by_zero",
+            ErrorKind::Arithmetic => "arithmetic",
+            ErrorKind::Assertion => "assertion",
+            ErrorKind::DidNotFail => "did_not_fail",
+            ErrorKind::UnexpectedFailure => "unexpected_failure",
+            ErrorKind::IterationLimit => "iteration_limit",
+            ErrorKind::RecursionLimit => "recursion_limit",
+            ErrorKind::Io => "io",
+        }
+    }
+}
+
+/// A range of bytes `start..end` within the source text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[must_use]
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    fn shifted(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+
+    /// The 1-based line number and column at which the span starts within `source`
+    #[must_use]
+    pub fn line_and_column(self, source: &str) -> (usize, usize) {
+        let start = self.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_number = source[..line_start].matches('\n').count() + 1;
+        (
+            line_number,
+            column(&source[line_start..], start - line_start),
+        )
+    }
+}
+
+/// The line in which an error occurred
+#[derive(Clone, Debug)]
+struct Location {
+    file: String,
+    line_number: usize,
+    line: String,
+    span: Span,
+}
+
+/// The place of an `@load` or a function call through which an error propagated
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Frame {
+    file: String,
+    line_number: usize,
+    column: usize,
+
+    /// The function called at this place, or `None` for an `@load`
+    function: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+
+    /// The offending part of the text currently being parsed,
+    /// which is resolved into `location` once the error leaves the line
+    span: Option<Span>,
+    location: Option<Box<Location>>,
+
+    /// The `@load`s and function calls through which the error propagated, innermost first,
+    /// each with the number of times in a row it was passed through, as happens in a recursion
+    stack_trace: Vec<(Frame, usize)>,
+
+    /// The function out of whose body the error has just propagated, to be recorded in the next frame
+    function: Option<String>,
+}
+
+/// The 1-based column of the byte offset `offset`
+fn column(line: &str, offset: usize) -> usize {
+    line.get(..offset).map_or(offset, |s| s.chars().count()) + 1
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            span: None,
+            location: None,
+            stack_trace: vec![],
+            function: None,
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The offending part of the text currently being executed, until the error is located
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Points the error at `span`, unless it already points at a more specific place
+    #[must_use]
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    /// Moves the span by `offset` bytes; used when the error comes from reading a part of the source text
+    #[must_use]
+    pub fn shifted(mut self, offset: usize) -> Self {
+        self.span = self.span.map(|span| span.shifted(offset));
+        self
+    }
+
+    /// Resolves the span within `source`, the content of `file`.
+    ///
+    /// An error that has already been located elsewhere came from a loaded file,
+    /// so that the place is instead recorded in the stack trace.
+    #[must_use]
+    pub fn locate(mut self, file: &str, source: &str) -> Self {
+        let span = self.span.take().unwrap_or(Span::new(0, 0));
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        let line_number = source[..line_start].matches('\n').count() + 1;
+        let span_in_line = Span::new(
+            start - line_start,
+            span.end.clamp(start, line_end) - line_start,
+        );
+
+        if self.location.is_none() {
+            self.location = Some(Box::new(Location {
+                file: file.to_owned(),
+                line_number,
+                line: line.to_owned(),
+                span: span_in_line,
+            }));
+        } else {
+            let frame = Frame {
+                file: file.to_owned(),
+                line_number,
+                column: column(line, span_in_line.start),
+                function: self.function.take(),
+            };
+            match self.stack_trace.last_mut() {
+                Some((last, repeated)) if *last == frame => *repeated += 1,
+                _ => self.stack_trace.push((frame, 1)),
+            }
+        }
+        self
+    }
+
+    /// Resolves the span within the body of the function `name`, defined in `file` whose content is `source`.
+    /// The place of the call is recorded in the stack trace once the error is located again.
+    #[must_use]
+    pub(crate) fn in_function(mut self, name: &str, file: &str, source: &str) -> Self {
+        self = self.locate(file, source);
+        self.function = Some(name.to_owned());
+        self
+    }
+
+    /// Renders the error in the style of rustc, with a caret under the offending part of the line
+    #[must_use]
+    pub fn render(&self, colored: bool) -> String {
+        let paint = |code: &str, s: &str| {
+            if colored {
+                format!("\x1b[{code}m{s}\x1b[00m")
+            } else {
+                s.to_owned()
+            }
+        };
+        let blue = |s: &str| paint("1;34", s);
+        let red = |s: &str| paint("1;31", s);
+
+        let header = format!(
+            "{}: {}",
+            red(&format!("error[{}]", self.kind.name())),
+            self.message
+        );
+
+        let Some(location) = &self.location else {
+            return header;
+        };
+        let Location {
+            file,
+            line_number,
+            line,
+            span,
+        } = location.as_ref();
+
+        let gutter = " ".repeat(line_number.to_string().len());
+        let start = span.start.min(line.len());
+        let end = span.end.clamp(start, line.len());
+        let start_column = column(line, start);
+        let caret_count = line[start..end].chars().count().max(1);
+
+        let mut lines = vec![
+            header,
+            format!(
+                "{gutter}{} {file}:{line_number}:{start_column}",
+                blue("-->")
+            ),
+            format!("{gutter} {}", blue("|")),
+            format!("{} {line}", blue(&format!("{line_number} |"))),
+            format!(
+                "{gutter} {} {}{}",
+                blue("|"),
+                " ".repeat(start_column - 1),
+                red(&"^".repeat(caret_count))
+            ),
+        ];
+        lines.extend(self.stack_trace.iter().map(|(frame, repeated)| {
+            let place = format!("{}:{}:{}", frame.file, frame.line_number, frame.column);
+            let note = match &frame.function {
+                Some(function) => format!("in `@{function}`, called from {place}"),
+                None => format!("loaded from {place}"),
+            };
+            let repeated = if *repeated > 1 {
+                format!(" ({repeated} times in a row)")
+            } else {
+                String::new()
+            };
+            format!("{gutter} {} note: {note}{repeated}", blue("="))
+        }));
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
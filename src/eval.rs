@@ -0,0 +1,591 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use big_s::S;
+use num_traits::{Signed, ToPrimitive};
+
+use crate::ast::{BinaryOp, BuiltinArgument, Expr, ExprKind, Program, UnaryOp};
+use crate::builtin::{Argument, Assertion, Block, Builtin, ExpectedFailure};
+use crate::environment::UserFunction;
+use crate::error::{Error, ErrorKind, Span};
+use crate::interpreter::{read_script, stack_frame_name, Interpreter, OutputFormat, Verbosity};
+use crate::parse::numeric_literal::{e_is_digit, parse_numeric_literal_with_radix_context};
+use crate::print::{format_json_summary, format_summary};
+use crate::quadratic;
+use crate::value::Value;
+
+impl Interpreter {
+    /// Executes the statements of `program`, whose source text is `source`.
+    ///
+    /// Each line is echoed right before the first statement on it is executed.
+    pub(crate) fn execute_program(&mut self, program: &Program, source: &str) -> Result<(), Error> {
+        // the offset of the first line that is not echoed yet
+        let mut echoed = 0;
+        for statement in &program.statements {
+            self.echo_lines(source, &mut echoed, statement.expr.span.end);
+
+            // A failed statement leaves `$_` and the radix context untouched
+            let (previous_value, radix_context) = (self.previous_value.clone(), self.radix_context);
+            match self.eval(&statement.expr) {
+                Ok(value) => self.previous_value = value,
+                Err(e) => {
+                    self.previous_value = previous_value;
+                    self.radix_context = radix_context;
+                    if self.test_mode && e.kind().is_assertion_failure() {
+                        let failure = e.locate(&self.source_name, source);
+                        self.test_report.failures.push(failure);
+                        continue;
+                    }
+                    return Err(e);
+                }
+            }
+
+            if statement.print && self.verbosity != Verbosity::Silent {
+                self.print_result(statement.expr.span, source);
+            }
+        }
+        self.echo_lines(source, &mut echoed, source.len());
+        Ok(())
+    }
+
+    /// Prints `$_`, the result of the code at `span` within `source`
+    fn print_result(&self, span: Span, source: &str) {
+        match self.output_format {
+            OutputFormat::Summary => print!(
+                "{}",
+                format_summary(
+                    &self.previous_value,
+                    self.radix_context,
+                    self.max_period_digits,
+                    self.colored
+                )
+            ),
+            OutputFormat::JsonLines => {
+                let (line, column) = span.line_and_column(source);
+                println!(
+                    "{}",
+                    format_json_summary(
+                        &self.previous_value,
+                        self.radix_context,
+                        self.max_period_digits,
+                        &self.source_name,
+                        line,
+                        column
+                    )
+                );
+            }
+        }
+    }
+
+    /// Echoes the lines of `source` from the offset `echoed` until the one containing the offset `end - 1`
+    pub(crate) fn echo_lines(&self, source: &str, echoed: &mut usize, end: usize) {
+        while *echoed < source.len() && *echoed < end {
+            let line = source[*echoed..].split('\n').next().unwrap_or_default();
+            *echoed += line.len() + 1;
+            if self.echo_input && self.is_verbose() {
+                println!(
+                    "{} {}",
+                    self.banner(self.colored),
+                    line.trim_end_matches('\r')
+                );
+            }
+        }
+    }
+
+    /// Evaluates `expr`, failing rather than overflowing the stack when the evaluation is nested too deeply,
+    /// be it by function calls, blocks or subexpressions
+    pub(crate) fn eval(&mut self, expr: &Expr) -> Result<Value, Error> {
+        if self.depth >= self.depth_limit {
+            let limit = self.depth_limit;
+            return Err(Error::new(
+                ErrorKind::RecursionLimit,
+                format!("The evaluation is nested more than {limit} levels deep; check that any recursion terminates"),
+            )
+            .with_span(expr.span));
+        }
+        self.depth += 1;
+        let result = self.eval_nested(expr);
+        self.depth -= 1;
+        result
+    }
+
+    fn eval_nested(&mut self, expr: &Expr) -> Result<Value, Error> {
+        match &expr.kind {
+            ExprKind::Literal(literal) => self.eval_numeric_literal(literal, expr.span),
+            ExprKind::SignedExponent { literal, sign } => {
+                self.eval_signed_exponent(literal, *sign, expr.span)
+            }
+            ExprKind::PreviousValue => Ok(self.previous_value.clone()),
+            ExprKind::Variable(name) => self.env.get(name).cloned().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::UndefinedVariable,
+                    format!("Undefined variable `${name}`"),
+                )
+                .with_span(expr.span)
+            }),
+            ExprKind::Assign(name, value) => {
+                let value = self.eval(value)?;
+                self.env.assign(name.clone(), value.clone());
+                Ok(value)
+            }
+            ExprKind::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                Ok(match op {
+                    UnaryOp::Plus => value,
+                    UnaryOp::Minus => -value,
+                    UnaryOp::Not => Value::from_bool(!value.is_truthy()),
+                })
+            }
+            ExprKind::Binary(op, lhs, rhs) => self.eval_binary(*op, lhs, rhs, expr.span),
+            ExprKind::Block(exprs) => self.eval_block(exprs),
+            ExprKind::RadixBlock(radix, exprs) => {
+                let stashed_radix_context = self.radix_context;
+                self.radix_context = *radix;
+                let value = self.eval_block(exprs);
+                self.radix_context = stashed_radix_context;
+                value
+            }
+            ExprKind::ContinuedFraction { terms, period } => {
+                self.eval_continued_fraction(terms, period.as_deref(), expr.span)
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.eval_if(condition, then_branch, else_branch.as_deref()),
+            ExprKind::Repeat { count, body } => self
+                .eval_repeat(count, body)
+                .map_err(|e| e.with_span(expr.span)),
+            ExprKind::While { condition, body } => self
+                .eval_while(condition, body)
+                .map_err(|e| e.with_span(expr.span)),
+            ExprKind::Builtin { builtin, args } => self
+                .eval_builtin(builtin, args)
+                .map_err(|e| e.with_span(expr.span)),
+            ExprKind::DefineFunction(function) => {
+                self.env.define_function(UserFunction {
+                    definition: Rc::clone(function),
+                    source_name: self.source_name.clone(),
+                });
+                Ok(self.previous_value.clone())
+            }
+            ExprKind::Call { name, args } => self
+                .eval_call(name, args)
+                .map_err(|e| e.with_span(expr.span)),
+        }
+    }
+
+    fn eval_binary(
+        &mut self,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+        span: Span,
+    ) -> Result<Value, Error> {
+        if let BinaryOp::And | BinaryOp::Or = op {
+            // the right-hand side is evaluated only when the left-hand side does not decide
+            let lhs = self.eval(lhs)?.is_truthy();
+            if lhs == (op == BinaryOp::Or) {
+                return Ok(Value::from_bool(lhs));
+            }
+            return Ok(Value::from_bool(self.eval(rhs)?.is_truthy()));
+        }
+
+        let lhs = self.eval(lhs)?;
+        let rhs = self.eval(rhs)?;
+        match op {
+            BinaryOp::Add => lhs.add(&rhs),
+            BinaryOp::Sub => lhs.sub(&rhs),
+            BinaryOp::Mul => lhs.mul(&rhs),
+            BinaryOp::Div => lhs.div(&rhs),
+            BinaryOp::Pow => lhs.pow(&rhs),
+            BinaryOp::Eq => Ok(Value::from_bool(lhs == rhs)),
+            BinaryOp::Ne => Ok(Value::from_bool(lhs != rhs)),
+            BinaryOp::Lt => lhs.compare(&rhs).map(|o| Value::from_bool(o.is_lt())),
+            BinaryOp::Le => lhs.compare(&rhs).map(|o| Value::from_bool(o.is_le())),
+            BinaryOp::Gt => lhs.compare(&rhs).map(|o| Value::from_bool(o.is_gt())),
+            BinaryOp::Ge => lhs.compare(&rhs).map(|o| Value::from_bool(o.is_ge())),
+            BinaryOp::And | BinaryOp::Or => unreachable!("short-circuited above"),
+        }
+        .map_err(|e| e.with_span(span))
+    }
+
+    /// Only the chosen branch is evaluated, as a block
+    fn eval_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &[Expr],
+        else_branch: Option<&[Expr]>,
+    ) -> Result<Value, Error> {
+        if self.eval(condition)?.is_truthy() {
+            self.eval_block(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.eval_block(else_branch)
+        } else {
+            // a false condition without `@else` leaves `$_` as it is
+            Ok(self.previous_value.clone())
+        }
+    }
+
+    /// Each iteration sets `$_` to its value, so that `$_` can serve as an accumulator.
+    /// The value of the loop is that of the last iteration, or `$_` when there is none.
+    fn eval_repeat(&mut self, count: &Expr, body: &[Expr]) -> Result<Value, Error> {
+        let count_value = self.eval(count)?;
+        let count = match &count_value {
+            Value::Rational(r) if r.is_integer() && !r.is_negative() => r.to_integer(),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Arithmetic,
+                    format!("`@repeat` expects a non-negative integer, not {count_value}"),
+                )
+                .with_span(count.span))
+            }
+        };
+        let count = count
+            .to_u64()
+            .filter(|&count| count <= self.iteration_limit)
+            .ok_or_else(|| self.iteration_limit_error("@repeat"))?;
+
+        for _ in 0..count {
+            self.previous_value = self.eval_block(body)?;
+        }
+        Ok(self.previous_value.clone())
+    }
+
+    /// Runs the body while the condition, evaluated before each iteration, is not zero;
+    /// `$_` is updated as in `@repeat`
+    fn eval_while(&mut self, condition: &Expr, body: &[Expr]) -> Result<Value, Error> {
+        let mut iterations = 0;
+        while self.eval(condition)?.is_truthy() {
+            if iterations == self.iteration_limit {
+                return Err(self.iteration_limit_error("@while"));
+            }
+            self.previous_value = self.eval_block(body)?;
+            iterations += 1;
+        }
+        Ok(self.previous_value.clone())
+    }
+
+    fn iteration_limit_error(&self, name: &str) -> Error {
+        Error::new(
+            ErrorKind::IterationLimit,
+            format!(
+                "`{name}` would run for more than {} iterations; raise the limit with `--max-iterations` if this is intended",
+                self.iteration_limit
+            ),
+        )
+    }
+
+    /// Evaluates the arguments in the caller's environment,
+    /// then the body with the parameters bound to them on top of the variables of the file
+    fn eval_call(&mut self, name: &str, args: &[Expr]) -> Result<Value, Error> {
+        let function = self.env.function(name).cloned().ok_or_else(|| {
+            Error::new(
+                ErrorKind::UnknownIdentifier,
+                format!("UNSUPPORTED IDENTIFIER found after `@`: `@{name}` is neither built in nor defined by `@fn`"),
+            )
+        })?;
+        let definition = &function.definition;
+        let arity = definition.params.len();
+        if args.len() != arity {
+            let plural = if arity == 1 { "" } else { "s" };
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                format!(
+                    "The function `@{name}` takes {arity} argument{plural} but was called with {}",
+                    args.len()
+                ),
+            ));
+        }
+        let mut arguments = HashMap::new();
+        for (param, arg) in definition.params.iter().zip(args) {
+            arguments.insert(param.clone(), self.eval(arg)?);
+        }
+        self.env.push_frame(arguments);
+        let result = self.eval_block(&definition.body);
+        self.env.pop_frame();
+        result.map_err(|e| e.in_function(name, &function.source_name, &definition.source))
+    }
+
+    /// Evaluates the expression arguments in order, then calls the built-in
+    fn eval_builtin(
+        &mut self,
+        builtin: &Builtin,
+        args: &[BuiltinArgument],
+    ) -> Result<Value, Error> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(match arg {
+                BuiltinArgument::Expression(expr) => Argument::Value(self.eval(expr)?),
+                BuiltinArgument::Other(arg) => arg.clone(),
+            });
+        }
+        builtin.call(self, values)
+    }
+
+    /// Returns the first argument when the assertion holds, as in `@assert_eq(7*6, 42) == 42`
+    pub(crate) fn eval_assertion(
+        &mut self,
+        assertion: Assertion,
+        mut values: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let holds = match assertion {
+            Assertion::NonZero => values[0].is_truthy(),
+            Assertion::Eq => values[0] == values[1],
+            Assertion::Ne => values[0] != values[1],
+            Assertion::Lt => values[0].compare(&values[1])? == Ordering::Less,
+            Assertion::Le => values[0].compare(&values[1])? != Ordering::Greater,
+            Assertion::Gt => values[0].compare(&values[1])? == Ordering::Greater,
+            Assertion::Ge => values[0].compare(&values[1])? != Ordering::Less,
+            Assertion::Approx => {
+                values[0].sub(&values[1])?.abs().compare(&values[2])? != Ordering::Greater
+            }
+        };
+        if holds {
+            self.test_report.passed += 1;
+            return Ok(values.swap_remove(0));
+        }
+
+        // each operand is shown in the radix context, followed by its decimal equivalent
+        let operands: Vec<String> = assertion
+            .operand_names()
+            .iter()
+            .zip(&values)
+            .map(|(name, value)| {
+                if self.radix_context == 10 {
+                    format!("{name}: {value}")
+                } else {
+                    format!(
+                        "{name}: {} # @decimal {{ {value} }}",
+                        value.to_str_radix(self.radix_context)
+                    )
+                }
+            })
+            .collect();
+        Err(Error::new(
+            ErrorKind::Assertion,
+            format!(
+                "ASSERTION FAILED: `{}`\n{}",
+                assertion.condition(),
+                operands.join("\n")
+            ),
+        ))
+    }
+
+    /// The value of the last expression
+    fn eval_block(&mut self, exprs: &[Expr]) -> Result<Value, Error> {
+        let mut value = None;
+        for expr in exprs {
+            value = Some(self.eval(expr)?);
+        }
+        Ok(value.expect("a block is never empty"))
+    }
+
+    /// A numeric literal is read in the radix context at the time of evaluation
+    fn eval_numeric_literal(&self, literal: &str, span: Span) -> Result<Value, Error> {
+        let (value, remaining) =
+            parse_numeric_literal_with_radix_context(literal, self.radix_context)
+                .map_err(|e| e.shifted(span.start))?;
+        if remaining.is_empty() {
+            return Ok(Value::Rational(value));
+        }
+
+        Err(Error::new(
+            ErrorKind::Syntax,
+            format!(
+                "`{literal}` is not a numeric literal in radix {}",
+                self.radix_context
+            ),
+        )
+        .with_span(span))
+    }
+
+    /// `1e-5` as a literal where `e` is not a digit, and as `(1e - 5)` where it is
+    fn eval_signed_exponent(&self, literal: &str, sign: usize, span: Span) -> Result<Value, Error> {
+        if !e_is_digit(self.radix_context) {
+            return self.eval_numeric_literal(literal, span);
+        }
+        let lhs =
+            self.eval_numeric_literal(&literal[..sign], Span::new(span.start, span.start + sign))?;
+        let rhs = self.eval_numeric_literal(
+            &literal[sign + 1..],
+            Span::new(span.start + sign + 1, span.end),
+        )?;
+        if literal[sign..].starts_with('-') {
+            lhs.sub(&rhs)
+        } else {
+            lhs.add(&rhs)
+        }
+    }
+
+    fn eval_continued_fraction(
+        &mut self,
+        terms: &[Expr],
+        period: Option<&[Expr]>,
+        span: Span,
+    ) -> Result<Value, Error> {
+        let mut values = vec![];
+        for term in terms {
+            values.push(self.eval(term)?);
+        }
+
+        let mut final_result = if let Some(period) = period {
+            let mut period_values = vec![];
+            for term in period {
+                period_values.push(self.eval(term)?);
+            }
+            quadratic::purely_periodic_continued_fraction(&period_values)
+                .map_err(|e| e.with_span(span))?
+        } else {
+            values
+                .pop()
+                .expect("This vector is not supposed to be empty")
+        };
+        for value in values.into_iter().rev() {
+            final_result = final_result
+                .recip()
+                .and_then(|recip| value.add(&recip))
+                .map_err(|e| e.with_span(span))?;
+        }
+        Ok(final_result)
+    }
+
+    /// `@load { "file"; ... }` when `dirty` is false, `@load_dirty { ... }` otherwise
+    pub(crate) fn load_files(
+        &mut self,
+        files: &[(String, Span)],
+        dirty: bool,
+    ) -> Result<Value, Error> {
+        let mut value = None;
+        for (filename, span) in files {
+            let loaded = if dirty {
+                self.load_single_file_dirty(filename)
+            } else {
+                self.load_single_file_clean(filename)
+            };
+            value = Some(loaded.map_err(|e| e.with_span(*span))?);
+        }
+        Ok(value.expect("a block is never empty"))
+    }
+
+    fn load_single_file_dirty(&mut self, filename: &str) -> Result<Value, Error> {
+        if self.is_verbose() {
+            println!(
+                "{}",
+                self.paint("2;34", &format!("##### Start of {filename}: "))
+            ); // faint blue
+        }
+
+        let content = read_script(filename)?;
+
+        // boot up the new interpreter, inheriting the environment
+        let mut new_ctx = self.child(self.previous_value.clone(), stack_frame_name(filename));
+        filename.clone_into(&mut new_ctx.source_name);
+
+        // the variables are shared with the loaded file, even when it fails midway
+        new_ctx.env = std::mem::take(&mut self.env);
+        let result = new_ctx.execute_lines(&content);
+        self.env = std::mem::take(&mut new_ctx.env);
+        self.test_report.merge(new_ctx.test_report);
+        let (value, radix_context) = result?;
+
+        self.previous_value = value.clone();
+
+        // write back the radix context
+        self.radix_context = radix_context;
+
+        if self.is_verbose() {
+            println!(
+                "{}",
+                self.paint("2;34", &format!("##### End of {filename}"))
+            ); // faint blue
+        }
+
+        Ok(value)
+    }
+
+    fn load_single_file_clean(&mut self, filename: &str) -> Result<Value, Error> {
+        // Boot up the interpreter with the default environment
+        // but keep track of the stack trace.
+        // Do not write back the radix context
+        let value = self.load_file_clean(filename, 10)?;
+
+        self.previous_value = value.clone();
+
+        Ok(value)
+    }
+
+    pub(crate) fn eval_should_fail(
+        &mut self,
+        body: &Block,
+        expected: Option<&ExpectedFailure>,
+    ) -> Result<Value, Error> {
+        if self.is_verbose() {
+            println!("{}", self.paint("2;31", "##### Start of ###should_fail###"));
+            // faint red
+        }
+
+        // boot up the new interpreter, inheriting the environment
+        let frame = self.paint("0;31", "###should_fail###");
+        let mut new_ctx = self.child(self.previous_value.clone(), frame);
+        new_ctx.env = self.env.clone();
+        new_ctx.source_name = S("###should_fail###");
+        // an assertion that fails inside is the failure being expected
+        new_ctx.test_mode = false;
+
+        let result = new_ctx.eval_block_argument(body);
+
+        if self.is_verbose() {
+            println!("{}", self.paint("2;31", "##### End of ###should_fail###"));
+        }
+        match result {
+            Err(e) => {
+                if let Some(expected) = expected {
+                    check_expected_failure(expected, &e)?;
+                }
+
+                if self.is_verbose() {
+                    println!(
+                        "As expected, failure occurred: '{}'",
+                        self.paint("4", e.message())
+                    );
+                }
+
+                self.test_report.passed += 1;
+
+                // $_ is the result of a successful computation preceding the failure
+                Ok(new_ctx.previous_value.clone())
+            }
+            Ok(value) => Err(Error::new(
+                ErrorKind::DidNotFail,
+                format!("`@should_fail` DID NOT FAIL; it instead succeeded with the following value: {value}"),
+            )),
+        }
+    }
+}
+
+/// Fails unless the error `actual` is the one described by the second argument of `@should_fail`
+fn check_expected_failure(expected: &ExpectedFailure, actual: &Error) -> Result<(), Error> {
+    let (matches, description) = match expected {
+        ExpectedFailure::Kind(kind) => (
+            actual.kind() == *kind,
+            format!("an error of kind `{}`", kind.name()),
+        ),
+        ExpectedFailure::Message(part) => (
+            actual.message().contains(part.as_str()),
+            format!("an error whose message contains {part:?}"),
+        ),
+    };
+    if matches {
+        return Ok(());
+    }
+    Err(Error::new(
+        ErrorKind::UnexpectedFailure,
+        format!(
+            "`@should_fail` FAILED DIFFERENTLY than expected:\nexpected: {description}\nactual: an error of kind `{}`: {}",
+            actual.kind().name(),
+            actual.message()
+        ),
+    ))
+}
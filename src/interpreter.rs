@@ -0,0 +1,422 @@
+use std::io::Read;
+use std::rc::Rc;
+
+use big_s::S;
+
+use crate::builtin::{Block, BlockBody, Builtin, Builtins};
+use crate::environment::Environment;
+use crate::error::{Error, ErrorKind};
+use crate::parse::{is_keyword, parse_program};
+use crate::print::DEFAULT_MAX_PERIOD_DIGITS;
+use crate::value::Value;
+
+/// The number of iterations after which a loop is stopped with an error, unless set otherwise
+pub const DEFAULT_ITERATION_LIMIT: u64 = 100_000;
+
+/// How deeply the evaluation can be nested unless set otherwise, so that a runaway recursion fails rather than overflowing the stack.
+///
+/// Each function call, block and subexpression being evaluated is one level, and a recursive call typically takes three.
+/// Each level is evaluated by native recursion and takes up to around 10 KiB of stack in an unoptimized build,
+/// so that this depth fits in half of the 2 MiB stack that Rust gives a spawned thread,
+/// leaving the other half to the parsing of a file loaded at that depth.
+/// A deeper limit set by [`Interpreter::set_depth_limit`] needs a thread with a larger stack.
+pub const DEFAULT_DEPTH_LIMIT: usize = 96;
+
+/// How much the interpreter writes to the standard output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Nothing at all, as is the case when embedded in another program
+    Silent,
+
+    /// Only the summary of each result
+    Quiet,
+
+    /// The input lines behind the banner, the summaries, and the entry to and exit from loaded files
+    Normal,
+}
+
+/// How each result is written to the standard output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The `frac:`, `cont:` and `digt:` lines for humans
+    Summary,
+
+    /// A JSON object on a single line for each result, with nothing else written to the standard output
+    JsonLines,
+}
+
+/// The outcome of the assertions, i.e. `@assert` and its variants such as `@assert_eq` and `@assert_approx`,
+/// and `@should_fail`, executed in test mode
+#[derive(Clone, Debug, Default)]
+pub struct TestReport {
+    pub passed: usize,
+
+    /// The failed assertions, located where they occurred
+    pub failures: Vec<Error>,
+}
+
+impl TestReport {
+    /// Adds the outcome of the assertions in a loaded file
+    pub(crate) fn merge(&mut self, other: TestReport) {
+        self.passed += other.passed;
+        self.failures.extend(other.failures);
+    }
+}
+
+/// Executes `PeriodiCode`, keeping `$_`, the radix context and the variables from one piece of code to the next.
+///
+/// Nested function calls are evaluated recursively on the calling thread, whose stack must be large enough
+/// for the depth limit; the default [`DEFAULT_DEPTH_LIMIT`] fits in the 2 MiB stack of a spawned thread.
+pub struct Interpreter {
+    pub(crate) previous_value: Value,
+    pub(crate) radix_context: u32,
+    pub(crate) stack_trace: Vec<String>,
+    pub(crate) env: Environment,
+
+    /// The name of the file being executed, which appears in error messages
+    pub(crate) source_name: String,
+
+    /// Whether each line is printed behind the banner before being executed.
+    /// The REPL turns this off, since the user has just typed the line in.
+    pub(crate) echo_input: bool,
+
+    pub(crate) verbosity: Verbosity,
+    pub(crate) output_format: OutputFormat,
+
+    /// Whether the output is decorated with ANSI escape sequences
+    pub(crate) colored: bool,
+
+    /// Whether a failed assertion is recorded in `test_report`, after which the execution resumes from the next statement
+    pub(crate) test_mode: bool,
+    pub(crate) test_report: TestReport,
+
+    /// The number of iterations after which `@repeat` or `@while` fails, so that a runaway loop does not hang
+    pub(crate) iteration_limit: u64,
+
+    /// The number of repeating digits beyond which a period is printed elided in the middle
+    pub(crate) max_period_digits: usize,
+
+    /// The number of nested levels being evaluated, including those of the interpreters this one is a child of
+    pub(crate) depth: usize,
+
+    /// The number of nested levels beyond which the evaluation fails, so that a runaway recursion does not overflow the stack
+    pub(crate) depth_limit: usize,
+
+    /// The built-in functions, shared with the child interpreters
+    pub(crate) builtins: Rc<Builtins>,
+}
+
+impl Interpreter {
+    /// A fresh interpreter with `$_ == 0` and no variables,
+    /// whose output is verbose and colored until told otherwise
+    #[must_use]
+    pub fn new(radix_context: u32) -> Self {
+        Self::with_context(Value::zero(), radix_context, vec![])
+    }
+
+    pub(crate) fn with_context(
+        previous_value: Value,
+        radix_context: u32,
+        stack_trace: Vec<String>,
+    ) -> Self {
+        Self {
+            previous_value,
+            radix_context,
+            stack_trace,
+            env: Environment::default(),
+            source_name: S("<command line>"),
+            echo_input: true,
+            verbosity: Verbosity::Normal,
+            output_format: OutputFormat::Summary,
+            colored: true,
+            test_mode: false,
+            test_report: TestReport::default(),
+            iteration_limit: DEFAULT_ITERATION_LIMIT,
+            max_period_digits: DEFAULT_MAX_PERIOD_DIGITS,
+            depth: 0,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            builtins: Rc::default(),
+        }
+    }
+
+    /// An interpreter for a loaded file or for `@should_fail`, which prints as much as `self` does, in the same colours
+    pub(crate) fn child(&self, previous_value: Value, frame: String) -> Self {
+        let mut stack_trace = self.stack_trace.clone();
+        stack_trace.push(frame);
+        let mut child = Self::with_context(previous_value, self.radix_context, stack_trace);
+        child.verbosity = self.verbosity;
+        child.output_format = self.output_format;
+        child.colored = self.colored;
+        child.test_mode = self.test_mode;
+        child.iteration_limit = self.iteration_limit;
+        child.max_period_digits = self.max_period_digits;
+        child.depth = self.depth;
+        child.depth_limit = self.depth_limit;
+        child.builtins = Rc::clone(&self.builtins);
+        child
+    }
+
+    /// `$_`, the result of the last computation
+    #[must_use]
+    pub fn previous_value(&self) -> &Value {
+        &self.previous_value
+    }
+
+    #[must_use]
+    pub fn radix_context(&self) -> u32 {
+        self.radix_context
+    }
+
+    /// Sets the name of the code that is executed next, which appears in error messages
+    pub fn set_source_name(&mut self, source_name: impl Into<String>) {
+        self.source_name = source_name.into();
+    }
+
+    /// Sets whether each line is printed behind the banner before being executed
+    pub fn set_echo_input(&mut self, echo_input: bool) {
+        self.echo_input = echo_input;
+    }
+
+    /// Sets how much is written to the standard output
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
+    }
+
+    /// Sets how each result is written to the standard output
+    pub fn set_output_format(&mut self, output_format: OutputFormat) {
+        self.output_format = output_format;
+    }
+
+    /// Whether the output is decorated with ANSI escape sequences
+    #[must_use]
+    pub fn colored(&self) -> bool {
+        self.colored
+    }
+
+    /// Sets whether the output is decorated with ANSI escape sequences
+    pub fn set_colored(&mut self, colored: bool) {
+        self.colored = colored;
+    }
+
+    /// Sets whether a failed assertion is recorded in the test report instead of stopping the execution,
+    /// which then resumes from the next statement
+    pub fn set_test_mode(&mut self, test_mode: bool) {
+        self.test_mode = test_mode;
+    }
+
+    /// The assertions executed in test mode so far, including those in loaded files
+    #[must_use]
+    pub fn test_report(&self) -> &TestReport {
+        &self.test_report
+    }
+
+    /// Sets the number of iterations after which a single `@repeat` or `@while` fails,
+    /// which is [`DEFAULT_ITERATION_LIMIT`] unless set otherwise
+    pub fn set_iteration_limit(&mut self, iteration_limit: u64) {
+        self.iteration_limit = iteration_limit;
+    }
+
+    /// Sets how deeply the evaluation can be nested before it fails, counting each function call, block and subexpression,
+    /// which is [`DEFAULT_DEPTH_LIMIT`] unless set otherwise.
+    ///
+    /// Each level takes up to around 10 KiB of stack in an unoptimized build,
+    /// so a deeper limit must come with a thread of a larger stack, as in
+    /// `std::thread::Builder::new().stack_size(256 * 1024 * 1024)` for 10000 levels.
+    pub fn set_depth_limit(&mut self, depth_limit: usize) {
+        self.depth_limit = depth_limit;
+    }
+
+    /// Sets the number of repeating digits beyond which a period is printed as its first and last digits and its length,
+    /// which is [`DEFAULT_MAX_PERIOD_DIGITS`](crate::DEFAULT_MAX_PERIOD_DIGITS) unless set otherwise
+    pub fn set_max_period_digits(&mut self, max_period_digits: usize) {
+        self.max_period_digits = max_period_digits;
+    }
+
+    /// Whether the input lines, the entry to loaded files and the like are printed
+    pub(crate) fn is_verbose(&self) -> bool {
+        self.verbosity == Verbosity::Normal && self.output_format == OutputFormat::Summary
+    }
+
+    /// `text` in the ANSI style `style` such as `2;34`, or as is when the output is not colored
+    pub(crate) fn paint(&self, style: &str, text: &str) -> String {
+        if self.colored {
+            format!("\x1b[{style}m{text}\x1b[00m")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// The banner shown in front of each line, e.g. `PeriodiCode:summary:base-10>`
+    ///
+    /// When `colored` is false, the ANSI escape sequences are omitted
+    #[must_use]
+    pub fn banner(&self, colored: bool) -> String {
+        if !colored {
+            let stack_trace_str = self.stack_trace.iter().fold(String::new(), |mut a, b| {
+                a += b;
+                a += ":";
+                a
+            });
+            return format!(
+                "PeriodiCode:{stack_trace_str}base-{:<2}>",
+                self.radix_context
+            );
+        }
+
+        let stack_trace_str = self.stack_trace.iter().fold(String::new(), |mut a, b| {
+            a += "\x1b[0;34m"; /* normal blue */
+            a += b;
+            a += "\x1b[00m:";
+            a
+        });
+
+        format!(
+            "\x1b[1;34mPeriodiCode\x1b[00m:{stack_trace_str}\x1b[{};32mbase-{:<2}\x1b[00m>",
+            if self.radix_context == 10 {
+                "0" /* normal */
+            } else {
+                "1;4" /* bold, underline */
+            },
+            self.radix_context,
+        )
+    }
+
+    /// Makes `@name(...)` call `builtin`, replacing any built-in function of the same name
+    ///
+    /// ```
+    /// use periodicode::{Argument, ArgumentKind, Builtin, Interpreter, Value, Verbosity};
+    ///
+    /// let mut ctx = Interpreter::new(10);
+    /// ctx.set_verbosity(Verbosity::Silent);
+    /// let double = Builtin::new("double", &[ArgumentKind::Expression], |_, args| match &args[..] {
+    ///     [Argument::Value(x)] => x.add(x),
+    ///     _ => unreachable!(),
+    /// });
+    /// ctx.register_builtin(double).unwrap();
+    /// assert_eq!(ctx.execute("@double(0.r3)").unwrap().to_string(), "2/3");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails when the name is that of a radix or has a syntax of its own, such as `@if`.
+    pub fn register_builtin(&mut self, builtin: Builtin) -> Result<(), Error> {
+        if is_keyword(builtin.name()) {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                format!(
+                    "`@{}` has a syntax of its own and cannot be a built-in function",
+                    builtin.name()
+                ),
+            ));
+        }
+        Rc::make_mut(&mut self.builtins).insert(builtin);
+        Ok(())
+    }
+
+    /// Evaluates a block given as an argument to a built-in function,
+    /// where each expression sets `$_` as a statement does, and returns the final `$_`
+    ///
+    /// # Errors
+    ///
+    /// Fails as the code in the block does; a block given as a string can also fail to parse.
+    pub fn eval_block_argument(&mut self, block: &Block) -> Result<Value, Error> {
+        match &block.0 {
+            BlockBody::Source(source) => self.execute_lines(source).map(|(value, _)| value),
+            BlockBody::Parsed(exprs) => {
+                for expr in exprs.iter() {
+                    self.previous_value = self.eval(expr)?;
+                }
+                Ok(self.previous_value.clone())
+            }
+        }
+    }
+
+    /// Parses and executes `input`, the whole content of the current source, and returns the final `$_`
+    ///
+    /// # Errors
+    ///
+    /// Returns the first syntax or runtime error, located within `input`.
+    /// `$_` and the radix context are those right before the failed statement.
+    pub fn execute(&mut self, input: &str) -> Result<Value, Error> {
+        self.execute_lines(input).map(|(value, _)| value)
+    }
+
+    /// Executes the file in a new interpreter starting from `$_ == 0` and the given radix context,
+    /// as if it were `@load`ed, and sets `$_` to its final value
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or its execution fails.
+    pub fn load_file(&mut self, filename: &str, radix_context: u32) -> Result<Value, Error> {
+        let value = self.load_file_clean(filename, radix_context)?;
+        self.previous_value = value.clone();
+        Ok(value)
+    }
+
+    /// Parses and executes `input`, the whole content of `self.source_name`
+    pub(crate) fn execute_lines(&mut self, input: &str) -> Result<(Value, u32), Error> {
+        let program = parse_program(input, &self.builtins, self.radix_context).map_err(|e| {
+            // show the lines up to the one with the syntax error
+            let end = e.span().map_or(input.len(), |span| span.start + 1);
+            self.echo_lines(input, &mut 0, end);
+            e.locate(&self.source_name, input)
+        })?;
+        self.execute_program(&program, input)
+            .map_err(|e| e.locate(&self.source_name, input))?;
+        Ok((self.previous_value.clone(), self.radix_context))
+    }
+
+    /// Executes the file in a new interpreter starting from `$_ == 0` and the given radix context,
+    /// keeping track of the stack trace. Returns the final value of `$_`.
+    pub(crate) fn load_file_clean(
+        &mut self,
+        filename: &str,
+        radix_context: u32,
+    ) -> Result<Value, Error> {
+        if self.is_verbose() {
+            println!(
+                "{}",
+                self.paint("2;34", &format!("##### Entering {filename}: "))
+            ); // faint blue
+        }
+
+        let content = read_script(filename)?;
+
+        let mut new_ctx = self.child(Value::zero(), stack_frame_name(filename));
+        new_ctx.radix_context = radix_context;
+        filename.clone_into(&mut new_ctx.source_name);
+        let result = new_ctx.execute_lines(&content);
+        self.test_report.merge(new_ctx.test_report);
+        let (value, _) = result?;
+
+        if self.is_verbose() {
+            println!(
+                "{}",
+                self.paint("2;34", &format!("##### Exiting {filename}"))
+            ); // faint blue
+        }
+
+        Ok(value)
+    }
+}
+
+pub(crate) fn read_script(filename: &str) -> Result<String, Error> {
+    let mut f = std::fs::File::open(filename)
+        .map_err(|_| Error::new(ErrorKind::Io, format!("File not found: {filename}")))?;
+    let mut content = String::new();
+    f.read_to_string(&mut content).map_err(|_| {
+        Error::new(
+            ErrorKind::Io,
+            format!("something went wrong reading the file {filename}"),
+        )
+    })?;
+    Ok(content)
+}
+
+/// The name under which a loaded file appears in the stack trace
+pub(crate) fn stack_frame_name(filename: &str) -> String {
+    filename
+        .strip_suffix(".periodicode")
+        .unwrap_or(filename)
+        .to_owned()
+}
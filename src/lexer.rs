@@ -0,0 +1,515 @@
+use crate::error::{Error, ErrorKind, Span};
+use crate::parse::numeric_literal::{
+    e_is_digit, literal_own_radix, parse_numeric_literal_with_radix_context, SEXAGESIMAL,
+};
+use crate::parse::radix_from_name;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// The raw text of a numeric literal such as `0x1.p-10` or `beef`,
+    /// which is only given a value once the radix context is known
+    Number(String),
+
+    /// `@name`, where whitespace is allowed after `@`
+    At(String),
+
+    /// `$name`
+    Dollar(String),
+
+    /// The name right after `@fn`, which is not read as a numeric literal
+    Identifier(String),
+
+    /// A string literal with its escape sequences resolved
+    Str(String),
+
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Equal,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
+    Bang,
+    Comma,
+    Semicolon,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Newline,
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_numeric_literal_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == ':'
+}
+
+/// An open `(`, `[` or `{`
+struct OpenBracket {
+    kind: TokenKind,
+
+    /// Whether the radix context inside is known to be sexagesimal,
+    /// as in `@sexagesimal { ... }` or `@base 60 { ... }`, where `;` may be a radix point
+    sexagesimal: bool,
+
+    /// Whether this `{` opens a block in a radix context of its own, which is left behind at its `}`
+    radix_block: bool,
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    tokens: Vec<Token>,
+
+    /// The `(`, `[` and `{` that are currently open, innermost last
+    open_brackets: Vec<OpenBracket>,
+
+    /// Whether the radix context outside any bracket is known to be sexagesimal,
+    /// as it is when the input is run with radix 60 or after `@set_radix(@sexagesimal)`
+    sexagesimal: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str, radix_context: u32) -> Self {
+        Self {
+            input,
+            pos: 0,
+            tokens: vec![],
+            open_brackets: vec![],
+            sexagesimal: radix_context == SEXAGESIMAL,
+        }
+    }
+
+    /// Whether the radix context at the current position is known to be sexagesimal
+    fn in_sexagesimal(&self) -> bool {
+        self.open_brackets
+            .last()
+            .map_or(self.sexagesimal, |bracket| bracket.sexagesimal)
+    }
+
+    /// Records whether the radix context from the current position on is sexagesimal,
+    /// up to the end of the innermost bracket
+    fn set_sexagesimal(&mut self, sexagesimal: bool) {
+        match self.open_brackets.last_mut() {
+            Some(bracket) => bracket.sexagesimal = sexagesimal,
+            None => self.sexagesimal = sexagesimal,
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Advances while `f` holds, returning the consumed text
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        let len = self.rest().find(|c| !f(c)).unwrap_or(self.rest().len());
+        self.pos += len;
+        &self.input[start..self.pos]
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize) {
+        self.tokens.push(Token {
+            kind,
+            span: Span::new(start, self.pos),
+        });
+    }
+
+    fn lex_identifier(&mut self, sigil: char, start: usize) -> Result<String, Error> {
+        let name = self.take_while(is_identifier_char).to_owned();
+        if name.is_empty() {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                format!("No identifier found after `{sigil}`"),
+            )
+            .with_span(Span::new(start, self.pos)));
+        }
+        Ok(name)
+    }
+
+    /// A numeric literal extends over letters, digits, dots and the colons separating the digits of a large radix,
+    /// and over the `;` and `,` of the Babylonian notation such as `1;24,51,10`.
+    /// A `#` right after a decimal integer also belongs to the literal, which is then in the radix given by the integer, as in `36#ZZ`,
+    /// provided that what follows is a valid literal in that radix; otherwise `#` starts a comment, as in `3#comment`.
+    ///
+    /// A sign right after an exponent marker `p` also belongs to the literal, as in `0x1.p-10`,
+    /// and so does one after `e` when the prefix of the literal makes `e` an exponent marker, as in `0d1e-5`.
+    /// Without a prefix, whether `1e-5` is a literal or `1e` minus 5 depends on the radix context,
+    /// so the sign is left to the parser, which makes a literal that holds both readings
+    fn lex_numeric_literal(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        self.take_while(is_numeric_literal_char);
+        let digits = &self.input[start..self.pos];
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            let mut rest = self.rest().chars();
+            if rest.next() == Some('#') && rest.next().is_some_and(is_numeric_literal_char) {
+                let digits_end = self.pos;
+                self.pos += 1;
+                self.take_while(is_numeric_literal_char);
+                if !self.is_radix_prefixed_literal(start) {
+                    self.pos = digits_end;
+                }
+            }
+        }
+        if self.at_babylonian_point(start)? {
+            self.pos += 1;
+            loop {
+                self.take_while(is_numeric_literal_char);
+                let mut rest = self.rest().chars();
+                if rest.next() == Some(',')
+                    && rest.next().is_some_and(|c| c.is_ascii_alphanumeric())
+                {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        let literal = &self.input[start..self.pos];
+        let ends_with_exponent_marker = literal.ends_with('p')
+            || (literal.ends_with('e')
+                && literal_own_radix(literal).is_some_and(|radix| !e_is_digit(radix)));
+        let mut rest = self.rest().chars();
+        if ends_with_exponent_marker
+            && matches!(rest.next(), Some('+' | '-'))
+            && rest.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        {
+            self.pos += 1;
+            self.take_while(is_numeric_literal_char);
+        }
+        Ok(self.input[start..self.pos].to_owned())
+    }
+
+    /// Whether the text from `start` so far, such as `36#ZZ`, is a valid literal in the radix before `#`,
+    /// possibly once the sign of an exponent that follows is included, as in `7#1e-2`
+    fn is_radix_prefixed_literal(&self, start: usize) -> bool {
+        let is_complete = |literal: &str| {
+            parse_numeric_literal_with_radix_context(literal, 10)
+                .is_ok_and(|(_, remaining)| remaining.is_empty())
+        };
+        let literal = &self.input[start..self.pos];
+        if is_complete(literal) {
+            return true;
+        }
+        let mut rest = self.rest().chars();
+        if !(literal.ends_with(['e', 'p'])
+            && matches!(rest.next(), Some('+' | '-'))
+            && rest.next().is_some_and(|c| c.is_ascii_alphanumeric()))
+        {
+            return false;
+        }
+        let exponent_len = self.rest()[1..]
+            .find(|c| !is_numeric_literal_char(c))
+            .unwrap_or(self.rest().len() - 1);
+        is_complete(&self.input[start..=self.pos + exponent_len])
+    }
+
+    /// Whether the literal from `start` so far is the integral part of a sexagesimal number in the Babylonian notation such as `1;24,51,10`,
+    /// where `;` is the radix point and `,` separates the fractional digits.
+    ///
+    /// The integral part must consist of decimal digits, optionally separated by `:`, and `;` must be followed by a digit or `r` without a space.
+    /// The notation is only read behind a `60#` prefix or in a sexagesimal radix context,
+    /// such as inside `@sexagesimal { ... }` or after `@set_radix(@sexagesimal)`,
+    /// and never inside `[...]`, where `;` ends the integral part of a continued fraction.
+    /// Elsewhere `;` separates statements, as in `{1;2}`, except that a `;` followed by what can only be sexagesimal places,
+    /// as in `1;24,51` or `1;r20`, is a syntax error rather than a silent change of meaning.
+    fn at_babylonian_point(&self, start: usize) -> Result<bool, Error> {
+        let literal = &self.input[start..self.pos];
+        let (prefix, integral) = literal
+            .split_once('#')
+            .map_or((None, literal), |(prefix, digits)| (Some(prefix), digits));
+        let mut rest = self.rest().chars();
+        let at_point = integral.starts_with(|c: char| c.is_ascii_digit())
+            && integral.chars().all(|c| c.is_ascii_digit() || c == ':')
+            && rest.next() == Some(';')
+            && rest.next().is_some_and(|c| c.is_ascii_digit() || c == 'r');
+        let in_brackets = self
+            .open_brackets
+            .last()
+            .is_some_and(|bracket| bracket.kind == TokenKind::LBracket);
+        if !at_point || in_brackets {
+            return Ok(false);
+        }
+        if prefix == Some("60") || self.in_sexagesimal() {
+            return Ok(true);
+        }
+        let after_point = &self.rest()[1..];
+        let places = after_point
+            .find(|c| !is_numeric_literal_char(c) && c != ',')
+            .map_or(after_point, |len| &after_point[..len]);
+        if places.starts_with('r') || places.contains(',') {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                format!(
+                    "`{integral};{places}` is only read in the Babylonian notation behind `60#` or in radix 60, as inside `@sexagesimal {{ ... }}`; \
+                     write `{integral}; {places}` for separate statements"
+                ),
+            )
+            .with_span(Span::new(start, self.pos + 1 + places.len())));
+        }
+        Ok(false)
+    }
+
+    /// The radix named by the last tokens, as in `@sexagesimal`, `@base 60` or `@radix(60)`,
+    /// together with the tokens before the name
+    fn radix_named_at_end(&self) -> Option<(&[Token], u32)> {
+        let radix_of = |literal: &str| literal.parse::<u32>().ok();
+        match self.tokens.as_slice() {
+            [before @ .., Token {
+                kind: TokenKind::At(name),
+                ..
+            }] => Some((before, radix_from_name(name)?)),
+            [before @ .., Token {
+                kind: TokenKind::At(name),
+                ..
+            }, Token {
+                kind: TokenKind::Number(literal),
+                ..
+            }] if name == "base" => Some((before, radix_of(literal)?)),
+            [before @ .., Token {
+                kind: TokenKind::At(name),
+                ..
+            }, Token {
+                kind: TokenKind::LParen,
+                ..
+            }, Token {
+                kind: TokenKind::Number(literal),
+                ..
+            }, Token {
+                kind: TokenKind::RParen,
+                ..
+            }] if name == "radix" => Some((before, radix_of(literal)?)),
+            _ => None,
+        }
+    }
+
+    /// Whether a `{` at the current position opens a block in a radix context of its own,
+    /// and if so, whether that is sexagesimal
+    fn opens_radix_block(&self) -> Option<bool> {
+        self.radix_named_at_end()
+            .map(|(_, radix)| radix == SEXAGESIMAL)
+    }
+
+    /// Whether a `)` at the current position closes `@set_radix(...)`,
+    /// and if so, whether the radix it sets is sexagesimal
+    fn closes_set_radix(&self) -> Option<bool> {
+        let (before, radix) = self.radix_named_at_end()?;
+        match before {
+            [.., Token {
+                kind: TokenKind::At(name),
+                ..
+            }, Token {
+                kind: TokenKind::LParen,
+                ..
+            }] if name == "set_radix" => Some(radix == SEXAGESIMAL),
+            _ => None,
+        }
+    }
+
+    /// Keeps track of the open brackets and of whether the radix context inside them is sexagesimal
+    fn track_bracket(&mut self, kind: &TokenKind) {
+        if matches!(
+            kind,
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace
+        ) {
+            let radix_block = if *kind == TokenKind::LBrace {
+                self.opens_radix_block()
+            } else {
+                None
+            };
+            self.open_brackets.push(OpenBracket {
+                kind: kind.clone(),
+                sexagesimal: radix_block.unwrap_or_else(|| self.in_sexagesimal()),
+                radix_block: radix_block.is_some(),
+            });
+            return;
+        }
+        let sets_radix = if *kind == TokenKind::RParen {
+            self.closes_set_radix()
+        } else {
+            None
+        };
+        let closed = self.open_brackets.pop();
+        // `@set_radix(...)` lasts beyond a block that is not in a radix context of its own
+        let left_behind = closed
+            .filter(|bracket| bracket.kind == TokenKind::LBrace && !bracket.radix_block)
+            .map(|bracket| bracket.sexagesimal);
+        if let Some(sexagesimal) = sets_radix.or(left_behind) {
+            self.set_sexagesimal(sexagesimal);
+        }
+    }
+
+    fn lex_string_literal(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        self.pos += 1; // the opening quote
+        let mut s = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(Error::new(ErrorKind::Syntax, "Unterminated string literal")
+                    .with_span(Span::new(start, self.pos)));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escape_start = self.pos - 1;
+                    let Some(escaped) = self.peek() else {
+                        return Err(Error::new(
+                            ErrorKind::Syntax,
+                            "Unterminated escape sequence inside a string literal",
+                        )
+                        .with_span(Span::new(start, self.pos)));
+                    };
+                    self.pos += escaped.len_utf8();
+                    match escaped {
+                        'n' => s.push('\n'),
+                        '"' | '\'' | '\\' => s.push(escaped),
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::Syntax,
+                                format!(
+                                "Unsupported escape sequence inside a string literal: \\{escaped}"
+                            ),
+                            )
+                            .with_span(Span::new(escape_start, self.pos)))
+                        }
+                    }
+                }
+                _ => s.push(c),
+            }
+        }
+    }
+
+    fn lex(&mut self) -> Result<(), Error> {
+        while let Some(c) = self.peek() {
+            let start = self.pos;
+            let two_char_operator = match self.rest().get(..2) {
+                Some("==") => Some(TokenKind::EqualEqual),
+                Some("!=") => Some(TokenKind::BangEqual),
+                Some("<=") => Some(TokenKind::LessEqual),
+                Some(">=") => Some(TokenKind::GreaterEqual),
+                Some("&&") => Some(TokenKind::AndAnd),
+                Some("||") => Some(TokenKind::OrOr),
+                _ => None,
+            };
+            if let Some(kind) = two_char_operator {
+                self.pos += 2;
+                self.push(kind, start);
+                continue;
+            }
+
+            let punctuation = match c {
+                '+' => Some(TokenKind::Plus),
+                '-' => Some(TokenKind::Minus),
+                '*' => Some(TokenKind::Star),
+                '/' => Some(TokenKind::Slash),
+                '^' => Some(TokenKind::Caret),
+                '=' => Some(TokenKind::Equal),
+                '<' => Some(TokenKind::Less),
+                '>' => Some(TokenKind::Greater),
+                '!' => Some(TokenKind::Bang),
+                ',' => Some(TokenKind::Comma),
+                ';' => Some(TokenKind::Semicolon),
+                '(' => Some(TokenKind::LParen),
+                ')' => Some(TokenKind::RParen),
+                '[' => Some(TokenKind::LBracket),
+                ']' => Some(TokenKind::RBracket),
+                '{' => Some(TokenKind::LBrace),
+                '}' => Some(TokenKind::RBrace),
+                '\n' => Some(TokenKind::Newline),
+                _ => None,
+            };
+            if let Some(kind) = punctuation {
+                self.pos += 1;
+                match kind {
+                    TokenKind::LParen
+                    | TokenKind::LBracket
+                    | TokenKind::LBrace
+                    | TokenKind::RParen
+                    | TokenKind::RBracket
+                    | TokenKind::RBrace => self.track_bracket(&kind),
+                    // a statement continues onto the next line while a bracket is open
+                    TokenKind::Newline if !self.open_brackets.is_empty() => continue,
+                    _ => {}
+                }
+                self.push(kind, start);
+            } else if c.is_whitespace() {
+                self.pos += c.len_utf8();
+            } else if c == '#' {
+                // a comment lasts until the end of the line
+                self.take_while(|c| c != '\n');
+            } else if c == '@' {
+                self.pos += 1;
+                self.take_while(|c| c == ' ' || c == '\t');
+                let name = self.lex_identifier('@', start)?;
+                let defines_function = name == "fn";
+                self.push(TokenKind::At(name), start);
+                if defines_function {
+                    // the name of the function being defined, as in `@fn square($x) { ... }`
+                    self.take_while(|c| c == ' ' || c == '\t');
+                    let start = self.pos;
+                    let name = self.take_while(is_identifier_char).to_owned();
+                    if !name.is_empty() {
+                        self.push(TokenKind::Identifier(name), start);
+                    }
+                }
+            } else if c == '$' {
+                self.pos += 1;
+                let name = self.lex_identifier('$', start)?;
+                self.push(TokenKind::Dollar(name), start);
+            } else if c == '"' {
+                let s = self.lex_string_literal()?;
+                self.push(TokenKind::Str(s), start);
+            } else if is_numeric_literal_char(c) {
+                let literal = self.lex_numeric_literal()?;
+                self.push(TokenKind::Number(literal), start);
+            } else {
+                return Err(
+                    Error::new(ErrorKind::Syntax, format!("Unexpected character `{c}`"))
+                        .with_span(Span::new(start, start + c.len_utf8())),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits the source text into tokens; comments and whitespace are dropped,
+/// except for the newlines outside brackets, which terminate statements.
+/// `radix_context` is the one in which the input starts, which decides where `;` is a Babylonian radix point
+pub fn tokenize(input: &str, radix_context: u32) -> Result<Vec<Token>, Error> {
+    let mut lexer = Lexer::new(input, radix_context);
+    lexer.lex()?;
+    Ok(lexer.tokens)
+}
+
+/// How many of `(`, `[` and `{` are left open at the end of `input`, which is 0 when `input` cannot be tokenized
+pub fn open_bracket_depth(input: &str) -> usize {
+    let mut lexer = Lexer::new(input, 10);
+    match lexer.lex() {
+        Ok(()) => lexer.open_brackets.len(),
+        Err(_) => 0,
+    }
+}
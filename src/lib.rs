@@ -0,0 +1,90 @@
+#![warn(clippy::pedantic)]
+
+//! `PeriodiCode`, a language for exact arithmetic with repeating decimals and periodic continued fractions
+//! in whichever radix you like.
+//!
+//! Besides the [`Interpreter`] behind the `periodicode` command,
+//! the numeric literals and the formatting of rationals are available on their own:
+//!
+//! ```
+//! use periodicode::{
+//!     evaluate, format_digit_expansion, parse_numeric_literal, Value, DEFAULT_MAX_PERIOD_DIGITS,
+//! };
+//!
+//! let (one_sixth, _) = parse_numeric_literal("0.1r6", 10).unwrap();
+//! assert_eq!(format_digit_expansion(&one_sixth, 12, DEFAULT_MAX_PERIOD_DIGITS), "0.2");
+//!
+//! let (value, radix) = evaluate("@set_radix(@hexadecimal); 0x1.p-10 * 10000", 10).unwrap();
+//! assert_eq!(value.to_string(), "1");
+//! assert_eq!(radix, 16);
+//! ```
+
+use num_rational::BigRational;
+
+mod ast;
+mod builtin;
+mod environment;
+mod error;
+mod eval;
+mod interpreter;
+mod lexer;
+mod number_theory;
+mod numerical_util;
+mod parse;
+mod print;
+mod quadratic;
+mod value;
+
+#[cfg(test)]
+mod test;
+
+pub use builtin::{Argument, ArgumentKind, Block, Builtin, BuiltinFunction, ExpectedFailure};
+pub use error::{Error, ErrorKind, Span};
+pub use interpreter::{
+    Interpreter, OutputFormat, TestReport, Verbosity, DEFAULT_DEPTH_LIMIT, DEFAULT_ITERATION_LIMIT,
+};
+pub use parse::radix_from_name;
+pub use print::{
+    format_continued_fraction, format_digit_expansion, format_fraction, format_json_summary,
+    format_periodic_continued_fraction, format_summary, write_summary, DEFAULT_MAX_PERIOD_DIGITS,
+};
+pub use quadratic::QuadraticIrrational;
+pub use value::Value;
+
+/// Parses the numeric literal at the beginning of `input`, such as `0.1r6` or `0x1.p-10`,
+/// returning its value and the rest of `input`.
+/// A literal without a prefix is read in `radix_context`.
+///
+/// # Errors
+///
+/// Fails when `input` does not begin with a numeric literal valid in `radix_context`.
+pub fn parse_numeric_literal(
+    input: &str,
+    radix_context: u32,
+) -> Result<(BigRational, &str), Error> {
+    parse::numeric_literal::parse_numeric_literal_with_radix_context(input, radix_context)
+}
+
+/// How many of `(`, `[` and `{` are left open at the end of `input`, ignoring those in strings and comments,
+/// by which an interactive session tells that a statement continues on the next line.
+/// The depth is 0 when `input` cannot be tokenized, so that the error is reported once it is executed.
+#[must_use]
+pub fn open_bracket_depth(input: &str) -> usize {
+    lexer::open_bracket_depth(input)
+}
+
+/// Executes `source` without printing anything, starting from `$_ == 0` in `radix_context`.
+/// Returns the final `$_` and the final radix context.
+///
+/// An evaluation nested more than [`DEFAULT_DEPTH_LIMIT`] levels deep fails, which keeps the recursion within the 2 MiB stack of a spawned thread;
+/// a deeper recursion needs an [`Interpreter`] with [`Interpreter::set_depth_limit`] on a thread with a larger stack.
+///
+/// # Errors
+///
+/// Returns the first syntax or runtime error.
+pub fn evaluate(source: &str, radix_context: u32) -> Result<(Value, u32), Error> {
+    let mut interpreter = Interpreter::new(radix_context);
+    interpreter.set_verbosity(Verbosity::Silent);
+    let value = interpreter.execute(source)?;
+    Ok((value, interpreter.radix_context()))
+}
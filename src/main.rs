@@ -1,135 +1,119 @@
 #![warn(clippy::pedantic)]
 
-use num_rational::BigRational;
-use num_traits::Zero;
-use parse::Parser;
-
-use crate::print::rational_print_summary;
-
-mod numerical_util;
-
-struct Interpreter {
-    previous_value: BigRational,
-    radix_context: u32,
-    stack_trace: Vec<String>,
+use std::io::{IsTerminal, Read};
+use std::process::ExitCode;
+
+use cli::{Options, Source};
+use periodicode::{Error, ErrorKind, Interpreter};
+
+mod cli;
+mod repl;
+mod test_runner;
+
+fn run(ctx: &mut Interpreter, options: &Options) -> Result<(), Error> {
+    for source in &options.sources {
+        match source {
+            // Each file behaves as if it were `@load`ed, except that it starts from the radix given on the command line
+            Source::File(filename) => {
+                ctx.load_file(filename, options.radix_context)?;
+            }
+            Source::Eval(code) => {
+                ctx.set_source_name("<command line>");
+                ctx.execute(code)?;
+            }
+            Source::Stdin => {
+                let mut content = String::new();
+                std::io::stdin().read_to_string(&mut content).map_err(|e| {
+                    Error::new(
+                        ErrorKind::Io,
+                        format!("something went wrong reading the standard input: {e}"),
+                    )
+                })?;
+                ctx.set_source_name("<stdin>");
+                ctx.execute(&content)?;
+            }
+        }
+    }
+    Ok(())
 }
 
-enum Judgement<T> {
-    EndOfLineEncountered,
-    ExpressionTerminatedWithSemicolon(T),
-    NoConsumption,
+/// The stack size of the thread running the interpreter, which evaluates nested function calls recursively
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// How deeply the evaluation can be nested on the thread of [`STACK_SIZE`],
+/// at up to around 10 KiB of stack for each level in an unoptimized build
+pub(crate) const DEPTH_LIMIT: usize = 10_000;
+
+fn main() -> ExitCode {
+    // the stack of the main thread is too small for a deep recursion in an unoptimized build
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_main)
+        .expect("cannot start the interpreter thread")
+        .join()
+        // the panic message has already been printed
+        .unwrap_or(ExitCode::FAILURE)
 }
 
-/// `f` is only run when the input has no trailing semicolon
-/// multiple semicolons are collapsed
-///
-/// `Judgement::EndOfLineEncountered` is returned when the line is terminated
-/// `Judgement::ExpressionTerminatedWithSemicolon(new_buf)` is returned when the preceding expression is terminated by a semicolon but the buffer still contains content
-/// `Judgement::NoConsumption` is returned when the buffer neither is empty nor begins with a semicolon
-fn judge_termination_or_semicolons<T>(buf: &str, f: T) -> Judgement<&str>
-where
-    T: Fn(),
-{
-    if buf.starts_with('#') || buf.is_empty() {
-        // No trailing semicolon
-        f();
-        Judgement::EndOfLineEncountered
-    } else if let Some(buf) = buf.strip_prefix(';') {
-        // collapse multiple semicolons
-        let mut remaining = buf.trim_start();
-        while let Some(buf) = remaining.strip_prefix(';') {
-            remaining = buf.trim_start();
+fn run_main() -> ExitCode {
+    let mut options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("error: {msg}\n\n{}", cli::USAGE);
+            return ExitCode::from(2);
         }
+    };
 
-        if remaining.is_empty() || remaining.starts_with('#') {
-            Judgement::EndOfLineEncountered
-        } else {
-            Judgement::ExpressionTerminatedWithSemicolon(remaining)
-        }
-    } else {
-        Judgement::NoConsumption
+    if options.help {
+        println!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
     }
-}
 
-impl Interpreter {
-    fn new(previous_value: BigRational, radix_context: u32, stack_trace: Vec<String>) -> Self {
-        Self {
-            previous_value,
-            radix_context,
-            stack_trace,
-        }
+    let colored = options.color.colored(std::io::stdout().is_terminal());
+    let colored_errors = options.color.colored(std::io::stderr().is_terminal());
+
+    if options.test {
+        return if test_runner::run(&options, colored, colored_errors) {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
     }
 
-    fn execute_line(&mut self, input: &str) -> Result<(), String> {
-        let stack_trace_str = self.stack_trace.iter().fold(String::new(), |mut a, b| {
-            a += "\x1b[0;34m"; /* normal blue */
-            a += b;
-            a += "\x1b[00m:";
-            a
-        });
-
-        let mut input = input.to_owned();
-        println!(
-            "\x1b[1;34mPeriodiCode\x1b[00m:{stack_trace_str}\x1b[{};32mbase-{:<2}\x1b[00m> {}",
-            if self.radix_context == 10 {
-                "0" /* normal */
-            } else {
-                "1;4" /* bold, underline */
-            },
-            self.radix_context,
-            input
-        );
-
-        match judge_termination_or_semicolons(input.trim_start(), || ()) {
-            Judgement::EndOfLineEncountered => return Ok(()),
-            Judgement::ExpressionTerminatedWithSemicolon(s) => input = s.to_owned(),
-            Judgement::NoConsumption => {}
+    if options.sources.is_empty() && !options.interactive {
+        if std::io::stdin().is_terminal() {
+            options.interactive = true;
+        } else {
+            options.sources.push(Source::Stdin);
         }
+    }
 
-        loop {
-            let mut p = Parser::new(
-                self.radix_context,
-                self.previous_value.clone(),
-                self.stack_trace.clone(),
-                &input,
-            );
-
-            self.previous_value = p.parse_expression()?;
-            self.radix_context = p.get_radix_context();
-            let remaining = p.get_buf().trim_start();
-
-            match judge_termination_or_semicolons(remaining, || {
-                rational_print_summary(&self.previous_value, self.radix_context);
-            }) {
-                Judgement::EndOfLineEncountered => return Ok(()),
-                Judgement::ExpressionTerminatedWithSemicolon(s) => input = s.to_owned(),
-                Judgement::NoConsumption => {
-                    return Err(format!("cannot parse the remaining `{remaining}`"))
-                }
-            }
-        }
+    let mut ctx = Interpreter::new(options.radix_context);
+    ctx.set_colored(colored);
+    ctx.set_iteration_limit(options.iteration_limit);
+    ctx.set_depth_limit(DEPTH_LIMIT);
+    ctx.set_max_period_digits(options.max_period_digits);
+    ctx.set_verbosity(options.verbosity);
+    ctx.set_output_format(options.output_format);
+
+    if let Err(e) = run(&mut ctx, &options) {
+        eprintln!("{}", e.render(colored_errors));
+        return ExitCode::FAILURE;
     }
 
-    fn execute_lines(&mut self, input: &str) -> Result<(BigRational, u32), String> {
-        for line in input.lines() {
-            self.execute_line(line)?;
+    if options.interactive {
+        if let Err(e) = repl::run(&mut ctx, colored_errors) {
+            if colored_errors {
+                eprintln!("\x1b[1;31merror\x1b[00m: {e}");
+            } else {
+                eprintln!("error: {e}");
+            }
+            return ExitCode::FAILURE;
         }
-        Ok((self.previous_value.clone(), self.radix_context))
     }
-}
 
-fn main() -> Result<(), String> {
-    let mut ctx = Interpreter::new(BigRational::zero(), 10, vec![]);
-    ctx.execute_lines(
-        r#"@load { "summary.periodicode" };
-$_"#,
-    )?;
-    Ok(())
+    ExitCode::SUCCESS
 }
 
 #[cfg(test)]
-mod test;
-
-mod print;
-
-mod parse;
+mod main_test;
@@ -0,0 +1,60 @@
+#[test]
+fn cli_options_test() {
+    use crate::cli::{ColorChoice, Options, Source};
+    use periodicode::{OutputFormat, Verbosity};
+    let parse = |args: &[&str]| Options::parse(args.iter().map(|s| (*s).to_owned()));
+
+    let options = parse(&["-r", "hex", "a.periodicode", "-e", "1/3", "-"]).unwrap();
+    assert_eq!(options.radix_context, 16);
+    assert_eq!(
+        options.sources,
+        vec![
+            Source::File("a.periodicode".to_owned()),
+            Source::Eval("1/3".to_owned()),
+            Source::Stdin
+        ]
+    );
+
+    assert_eq!(parse(&[]).unwrap().sources, vec![]);
+    assert!(parse(&["-i"]).unwrap().interactive);
+    assert_eq!(parse(&["--radix", "12"]).unwrap().radix_context, 12);
+    assert_eq!(parse(&["--radix", "36"]).unwrap().radix_context, 36);
+    assert!(parse(&["--radix", "1"]).is_err());
+    assert!(parse(&["--radix"]).is_err());
+    assert!(parse(&["--unknown"]).is_err());
+
+    let options = parse(&["-q", "--no-color"]).unwrap();
+    assert_eq!(options.verbosity, Verbosity::Quiet);
+    assert_eq!(options.color, ColorChoice::Never);
+    assert_eq!(parse(&[]).unwrap().color, ColorChoice::Auto);
+    assert_eq!(
+        parse(&["--json"]).unwrap().output_format,
+        OutputFormat::JsonLines
+    );
+    assert_eq!(parse(&["--color"]).unwrap().color, ColorChoice::Always);
+    assert!(ColorChoice::Always.colored(false));
+    assert!(!ColorChoice::Never.colored(true));
+    assert!(!ColorChoice::Auto.colored(false));
+
+    assert_eq!(
+        parse(&["--max-iterations", "10"]).unwrap().iteration_limit,
+        10
+    );
+    assert!(parse(&["--max-iterations", "-1"]).is_err());
+    assert_eq!(
+        parse(&["--max-period-digits", "6"])
+            .unwrap()
+            .max_period_digits,
+        6
+    );
+    assert!(parse(&["--max-period-digits"]).is_err());
+
+    let options = parse(&["test", "-r", "12", "tests", "a.periodicode"]).unwrap();
+    assert!(options.test);
+    assert_eq!(options.radix_context, 12);
+    assert_eq!(options.sources.len(), 2);
+    assert!(!parse(&["a.periodicode", "test"]).unwrap().test);
+    assert_eq!(parse(&["-r", "60"]).unwrap().radix_context, 60);
+    assert!(parse(&["test"]).is_err());
+    assert!(parse(&["test", "-e", "1"]).is_err());
+}
@@ -0,0 +1,233 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Splits a positive denominator `q` into `(q1, q2)` with `q == q1 * q2`,
+/// where every prime factor of `q1` divides `radix` and `q2` is coprime to `radix`.
+///
+/// In the expansion of `p/q`, `q1` decides how many digits precede the period and `q2` how long the period is.
+pub(crate) fn split_by_radix(q: &BigInt, radix: u32) -> (BigInt, BigInt) {
+    let mut coprime = q.clone();
+    let mut common = coprime.gcd(&BigInt::from(radix));
+    // squaring `common` removes the factors of the radix in logarithmically many steps
+    while !common.is_one() {
+        coprime /= &common;
+        common = coprime.gcd(&(&common * &common));
+    }
+    (q / &coprime, coprime)
+}
+
+/// The number of digits before the period and the length of the period
+/// in the radix-`radix` expansion of a fraction in lowest terms whose denominator is `q`.
+/// The period is 0 when the expansion terminates.
+pub(crate) fn expansion_lengths(q: &BigInt, radix: u32) -> (u64, BigInt) {
+    let (q1, q2) = split_by_radix(q, radix);
+    let period = if q2.is_one() {
+        BigInt::zero()
+    } else {
+        multiplicative_order(radix, &q2)
+    };
+    (preperiod_length(&q1, radix), period)
+}
+
+/// The exponent of the prime `p` in the factorization of `n`
+fn valuation(n: &BigInt, p: &BigInt) -> u64 {
+    let mut n = n.clone();
+    // divide by p, p^2, p^4, ... for as long as possible, and then by the same powers in reverse
+    let mut powers = vec![];
+    let mut power = p.clone();
+    while n.is_multiple_of(&power) {
+        n /= &power;
+        let squared = &power * &power;
+        powers.push(power);
+        power = squared;
+    }
+    let mut valuation = (1 << powers.len()) - 1;
+    for (i, power) in powers.iter().enumerate().rev() {
+        if n.is_multiple_of(power) {
+            n /= power;
+            valuation += 1 << i;
+        }
+    }
+    valuation
+}
+
+/// The number of digits before the period in radix `radix` of a fraction whose denominator is `q1`,
+/// all of whose prime factors divide `radix`: the least `k` such that `q1` divides `radix^k`
+pub(crate) fn preperiod_length(q1: &BigInt, radix: u32) -> u64 {
+    factorize(&BigInt::from(radix))
+        .into_iter()
+        .map(|(p, exponent)| valuation(q1, &p).div_ceil(u64::from(exponent)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// The least `k > 0` such that `radix^k ≡ 1 (mod n)`, where `n` is positive and coprime to `radix`,
+/// which is the length of the period in radix `radix` of a fraction whose denominator is `n`
+pub(crate) fn multiplicative_order(radix: u32, n: &BigInt) -> BigInt {
+    // Carmichael's function λ(n) is a multiple of the order, whose prime factors are collected along the way
+    let mut lambda = BigInt::one();
+    let mut primes: Vec<BigInt> = vec![];
+    for (p, exponent) in factorize(n) {
+        let lambda_of_power = if p == BigInt::from(2) {
+            match exponent {
+                1 => BigInt::one(),
+                2 => BigInt::from(2),
+                _ => BigInt::one() << (exponent - 2),
+            }
+        } else {
+            p.pow(exponent - 1) * (&p - 1)
+        };
+        lambda = lambda.lcm(&lambda_of_power);
+        if exponent > 1 || p == BigInt::from(2) {
+            primes.push(p.clone());
+        }
+        primes.extend(factorize(&(&p - 1)).into_iter().map(|(q, _)| q));
+    }
+    primes.sort();
+    primes.dedup();
+
+    let radix = BigInt::from(radix);
+    let mut order = lambda;
+    for q in &primes {
+        while order.is_multiple_of(q) && radix.modpow(&(&order / q), n).is_one() {
+            order /= q;
+        }
+    }
+    order
+}
+
+/// The prime factors of a positive integer with their exponents, in no particular order
+fn factorize(n: &BigInt) -> Vec<(BigInt, u32)> {
+    let (factors, _) = factorize_with_effort(n, usize::MAX);
+    factors
+}
+
+/// The prime factors of a positive integer with their exponents, in no particular order,
+/// as far as Pollard's rho algorithm finds them in `max_steps` steps for each composite;
+/// the composites that it could not split are returned apart, and have no prime factor below 1000
+pub(crate) fn factorize_with_effort(
+    n: &BigInt,
+    max_steps: usize,
+) -> (Vec<(BigInt, u32)>, Vec<BigInt>) {
+    let mut unsplit = vec![];
+    let mut factors: Vec<(BigInt, u32)> = vec![];
+    let mut add = |p: BigInt, exponent: u32| match factors.iter_mut().find(|(q, _)| *q == p) {
+        Some((_, e)) => *e += exponent,
+        None => factors.push((p, exponent)),
+    };
+
+    let mut n = n.clone();
+    for p in (2..1000u32).filter(|&p| p == 2 || p % 2 == 1) {
+        let p = BigInt::from(p);
+        if &p * &p > n {
+            break;
+        }
+        let mut exponent = 0;
+        while n.is_multiple_of(&p) {
+            n /= &p;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            add(p, exponent);
+        }
+    }
+
+    // what remains has no factor below 1000
+    let mut composites = vec![n];
+    while let Some(n) = composites.pop() {
+        if n.is_one() {
+            continue;
+        }
+        if is_probable_prime(&n) {
+            add(n, 1);
+        } else if let Some(divisor) = pollard_brent(&n, max_steps) {
+            composites.push(&n / &divisor);
+            composites.push(divisor);
+        } else {
+            unsplit.push(n);
+        }
+    }
+    (factors, unsplit)
+}
+
+/// The Miller–Rabin test with the first twelve primes as bases,
+/// which is exact below 3.3 * 10^24 and practically so above
+fn is_probable_prime(n: &BigInt) -> bool {
+    const BASES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < &BigInt::from(2) {
+        return false;
+    }
+    if let Some(small) = n.to_u32() {
+        if BASES.contains(&small) {
+            return true;
+        }
+    }
+    let n_minus_one: BigInt = n - 1;
+    let twos = n_minus_one.trailing_zeros().unwrap_or(0);
+    let odd = &n_minus_one >> twos;
+    BASES.iter().all(|&base| {
+        let mut x = BigInt::from(base).modpow(&odd, n);
+        if x.is_one() || x == n_minus_one {
+            return true;
+        }
+        for _ in 1..twos {
+            x = &x * &x % n;
+            if x == n_minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// A nontrivial divisor of an odd composite `n` by Brent's variant of Pollard's rho algorithm,
+/// or `None` when none is found in about `max_steps` steps of the sequence
+fn pollard_brent(n: &BigInt, max_steps: usize) -> Option<BigInt> {
+    const BATCH: usize = 128;
+    let mut steps = 0;
+    for c in 1u32.. {
+        let step = |z: &BigInt| (z * z + c) % n;
+        let mut fast = BigInt::from(2);
+        let mut slow = fast.clone();
+        let mut saved = fast.clone();
+        let mut product = BigInt::one();
+        let mut divisor = BigInt::one();
+        let mut length = 1;
+        while divisor.is_one() {
+            if steps >= max_steps {
+                return None;
+            }
+            steps = steps.saturating_add(2 * length);
+            slow.clone_from(&fast);
+            for _ in 0..length {
+                fast = step(&fast);
+            }
+            let mut k = 0;
+            while k < length && divisor.is_one() {
+                saved.clone_from(&fast);
+                for _ in 0..BATCH.min(length - k) {
+                    fast = step(&fast);
+                    product = product * (&slow - &fast).abs() % n;
+                }
+                divisor = product.gcd(n);
+                k += BATCH;
+            }
+            length *= 2;
+        }
+        if &divisor == n {
+            // the batch overshot; retrace it one step at a time
+            loop {
+                saved = step(&saved);
+                divisor = (&slow - &saved).abs().gcd(n);
+                if !divisor.is_one() {
+                    break;
+                }
+            }
+        }
+        if &divisor != n && !divisor.is_zero() {
+            return Some(divisor);
+        }
+    }
+    unreachable!("some constant of the polynomial finds a divisor")
+}
@@ -1,476 +1,852 @@
-use big_s::S;
-use num_bigint::BigInt;
-use num_rational::BigRational;
-use num_traits::{One, Zero};
-use once_cell::sync::Lazy;
-use regex::Regex;
-use std::io::Read;
-
-use crate::{judge_termination_or_semicolons, Interpreter, Judgement};
+use std::rc::Rc;
+
+use crate::ast::{
+    BinaryOp, BuiltinArgument, Expr, ExprKind, Function, Program, Statement, UnaryOp,
+};
+use crate::builtin::{
+    Argument, ArgumentKind, Block, BlockBody, Builtin, Builtins, ExpectedFailure,
+};
+use crate::error::{Error, ErrorKind, Span};
+use crate::lexer::{tokenize, Token, TokenKind};
+use numeric_literal::literal_own_radix;
 
 pub mod numeric_literal;
 
-type Value = BigRational;
+/// How deeply the code can be nested, counting each expression inside another,
+/// so that the recursive descent fails rather than overflowing the stack.
+/// Each level takes up to around 30 KiB of stack in an unoptimized build,
+/// so that this depth leaves half of the 2 MiB stack of a spawned thread to the evaluation.
+const MAX_NESTING_DEPTH: usize = 32;
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+
+    /// The built-in functions, whose arguments are parsed according to their kinds
+    builtins: &'a Builtins,
 
-pub struct Parser<'a> {
+    /// A copy of `source` shared by the functions defined in it
+    shared_source: Option<Rc<str>>,
+
+    /// Whether the body of an `@fn` is being parsed, in which another `@fn` is not allowed
+    in_function_body: bool,
+
+    /// The number of expressions being parsed inside one another
+    depth: usize,
+}
+
+/// Parses a whole program that starts in `radix_context`,
+/// in which `@name(...)` calls a built-in function when `builtins` has one of that name
+pub(crate) fn parse_program(
+    source: &str,
+    builtins: &Builtins,
     radix_context: u32,
-    previous_value: Value,
-    stack_trace: Vec<String>,
-    buf: &'a str,
+) -> Result<Program, Error> {
+    let mut parser = Parser {
+        source,
+        tokens: tokenize(source, radix_context)?,
+        pos: 0,
+        builtins,
+        shared_source: None,
+        in_function_body: false,
+        depth: 0,
+    };
+    parser.parse_program()
 }
 
-struct Identifier(String);
+impl Parser<'_> {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + n).map(|token| &token.kind)
+    }
+
+    /// The span of the next token, or an empty span right after the last token at the end of the input
+    fn peek_span(&self) -> Span {
+        if let Some(token) = self.tokens.get(self.pos) {
+            return token.span;
+        }
+        let end = self.tokens.last().map_or(0, |token| token.span.end);
+        Span::new(end, end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// The span from `start` to the end of the last consumed token
+    fn span_from(&self, start: usize) -> Span {
+        let end = self.tokens[..self.pos]
+            .last()
+            .map_or(start, |token| token.span.end);
+        Span::new(start, end.max(start))
+    }
 
-impl<'b> Parser<'b> {
-    pub fn get_buf(&self) -> &str {
-        self.buf
+    fn error_here(&self, kind: ErrorKind, msg: impl Into<String>) -> Error {
+        Error::new(kind, msg).with_span(self.peek_span())
     }
-    pub fn new(
-        radix_context: u32,
-        previous_value: Value,
-        stack_trace: Vec<String>,
-        buf: &'b str,
-    ) -> Self {
-        assert!(
-            radix_context <= 25,
-            "radix greater than 25 is not supported"
-        );
 
-        Self {
-            radix_context,
-            previous_value,
-            stack_trace,
-            buf,
+    /// Runs `f` one level deeper in the nesting of the code, failing beyond [`MAX_NESTING_DEPTH`]
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(self.error_here(
+                ErrorKind::RecursionLimit,
+                format!("The code is nested more than {MAX_NESTING_DEPTH} levels deep"),
+            ));
         }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
     }
 
-    pub fn get_radix_context(&mut self) -> u32 {
-        self.radix_context
+    fn consume_or_err(&mut self, kind: &TokenKind, msg: impl Into<String>) -> Result<Span, Error> {
+        if self.peek() == Some(kind) {
+            Ok(self.next().expect("peeked").span)
+        } else {
+            Err(self.error_here(ErrorKind::Syntax, msg))
+        }
     }
 
-    pub fn parse_expression(&mut self) -> Result<Value, String> {
-        self.trim_start();
-        self.parse_additive_expression()
+    fn skip_semicolons(&mut self) {
+        while self.peek() == Some(&TokenKind::Semicolon) {
+            self.pos += 1;
+        }
     }
 
-    fn parse_additive_expression(&mut self) -> Result<Value, String> {
-        self.trim_start();
-        let mut val = self.parse_multiplicative_expression()?;
+    fn parse_program(&mut self) -> Result<Program, Error> {
+        let mut statements = vec![];
         loop {
-            if let Some(stripped) = self.buf.trim_start().strip_prefix('+') {
-                self.buf = stripped;
-                let val2 = self.parse_multiplicative_expression()?;
-                val += val2;
-            } else if let Some(stripped) = self.buf.trim_start().strip_prefix('-') {
-                self.buf = stripped;
-                let val2 = self.parse_multiplicative_expression()?;
-                val -= val2;
-            } else {
-                break;
+            // empty statements are simply ignored
+            while matches!(self.peek(), Some(TokenKind::Semicolon | TokenKind::Newline)) {
+                self.pos += 1;
             }
+            if self.peek().is_none() {
+                return Ok(Program { statements });
+            }
+
+            let expr = self.parse_expression()?;
+            let print = match self.peek() {
+                None | Some(TokenKind::Newline) => true,
+                Some(TokenKind::Semicolon) => false,
+                Some(_) => {
+                    let start = self.peek_span().start;
+                    let line_end = self.source[start..]
+                        .find('\n')
+                        .map_or(self.source.len(), |i| start + i);
+                    let remaining = self.source[start..line_end].trim_end();
+                    return Err(Error::new(
+                        ErrorKind::Syntax,
+                        format!("cannot parse the remaining `{remaining}`"),
+                    )
+                    .with_span(Span::new(start, start + remaining.len())));
+                }
+            };
+            // a definition has no result worth printing
+            let print = print && !matches!(expr.kind, ExprKind::DefineFunction(_));
+            statements.push(Statement { expr, print });
         }
+    }
 
-        Ok(val)
+    fn parse_expression(&mut self) -> Result<Expr, Error> {
+        self.nested(Self::parse_assignment_expression)
     }
 
-    fn parse_multiplicative_expression(&mut self) -> Result<Value, String> {
-        self.trim_start();
-        let mut val = self.parse_unary_expression()?;
-        loop {
-            if let Some(stripped) = self.buf.trim_start().strip_prefix('*') {
-                self.buf = stripped;
-                let val2 = self.parse_unary_expression()?;
-                val *= val2;
-            } else if let Some(stripped) = self.buf.trim_start().strip_prefix('/') {
-                self.buf = stripped;
-                let val2 = self.parse_unary_expression()?;
-                val /= val2;
-            } else {
-                break;
+    fn parse_assignment_expression(&mut self) -> Result<Expr, Error> {
+        if let (Some(TokenKind::Dollar(name)), Some(TokenKind::Equal)) =
+            (self.peek(), self.peek_nth(1))
+        {
+            if name == "_" {
+                return Err(self.error_here(
+                    ErrorKind::Syntax,
+                    "Cannot assign to `$_`, which always holds the result of the previous computation",
+                ));
             }
+            let name = name.clone();
+            let start = self.peek_span().start;
+            self.pos += 2;
+
+            // `$a = $b = 3` assigns 3 to both
+            let value = self.parse_expression()?;
+            return Ok(Expr {
+                kind: ExprKind::Assign(name, Box::new(value)),
+                span: self.span_from(start),
+            });
         }
-        Ok(val)
+        self.parse_or_expression()
     }
 
-    fn parse_unary_expression(&mut self) -> Result<Value, String> {
-        let buf = self.buf.trim_start();
-        if let Some(buf) = buf.strip_prefix('+') {
-            self.buf = buf;
-            let value = self.parse_unary_expression()?;
-            Ok(value)
-        } else if let Some(buf) = buf.strip_prefix('-') {
-            self.buf = buf;
-            let value = self.parse_unary_expression()?;
-            Ok(-value)
-        } else {
-            self.parse_funccall_or_decorated_block()
+    fn binary(&self, op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        let span = self.span_from(lhs.span.start);
+        Expr {
+            kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+            span,
         }
     }
 
-    fn consume_char_or_err(&mut self, c: char, msg: &'static str) -> Result<(), String> {
-        self.trim_start();
-        if let Some(buf_) = self.buf.strip_prefix(c) {
-            self.buf = buf_.trim_start();
-            Ok(())
-        } else {
-            Err(S(msg))
+    fn parse_or_expression(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_and_expression()?;
+        while self.peek() == Some(&TokenKind::OrOr) {
+            self.pos += 1;
+            let rhs = self.parse_and_expression()?;
+            expr = self.binary(BinaryOp::Or, expr, rhs);
         }
+        Ok(expr)
     }
 
-    fn trim_start(&mut self) {
-        self.buf = self.buf.trim_start();
+    fn parse_and_expression(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_comparison_expression()?;
+        while self.peek() == Some(&TokenKind::AndAnd) {
+            self.pos += 1;
+            let rhs = self.parse_comparison_expression()?;
+            expr = self.binary(BinaryOp::And, expr, rhs);
+        }
+        Ok(expr)
     }
 
-    fn parse_string_literal(&mut self) -> Result<String, String> {
-        self.trim_start();
-        let mut s = String::new();
-        if let Some(buf_) = self.buf.strip_prefix('"') {
-            let mut char_indices = buf_.char_indices();
-            let closing_quote_index = loop {
-                match char_indices.next() {
-                    Some((i, '"')) => {
-                        break i;
-                    }
-                    Some((_, '\\')) => match char_indices.next() {
-                        Some((_, 'n')) => {
-                            s.push('\n');
-                        }
-                        Some((_, '"')) => {
-                            s.push('\"');
-                        }
-                        Some((_, '\'')) => {
-                            s.push('\'');
-                        }
-                        Some((_, '\\')) => {
-                            s.push('\\');
-                        }
-                        None => {
-                            return Err(S("Unterminated escape sequence inside a string literal"))
-                        }
-                        Some((_, c)) => {
-                            return Err(format!(
-                                "Unsupported escape sequence inside a string literal: \\{c}"
-                            ))
-                        }
-                    },
-                    Some((_, c)) => s.push(c),
-                    None => return Err(S("Unterminated string literal")),
-                }
+    fn comparison_operator(&self) -> Option<BinaryOp> {
+        match self.peek()? {
+            TokenKind::EqualEqual => Some(BinaryOp::Eq),
+            TokenKind::BangEqual => Some(BinaryOp::Ne),
+            TokenKind::Less => Some(BinaryOp::Lt),
+            TokenKind::LessEqual => Some(BinaryOp::Le),
+            TokenKind::Greater => Some(BinaryOp::Gt),
+            TokenKind::GreaterEqual => Some(BinaryOp::Ge),
+            _ => None,
+        }
+    }
+
+    /// The comparisons do not associate, since `1 < 2 < 3` would compare `1 < 2`, i.e. 1, with 3
+    fn parse_comparison_expression(&mut self) -> Result<Expr, Error> {
+        let lhs = self.parse_additive_expression()?;
+        let Some(op) = self.comparison_operator() else {
+            return Ok(lhs);
+        };
+        self.pos += 1;
+        let rhs = self.parse_additive_expression()?;
+        if self.comparison_operator().is_some() {
+            return Err(self.error_here(
+                ErrorKind::Syntax,
+                "Comparisons cannot be chained; combine them with `&&` instead",
+            ));
+        }
+        Ok(self.binary(op, lhs, rhs))
+    }
+
+    fn parse_additive_expression(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_multiplicative_expression()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Plus) => BinaryOp::Add,
+                Some(TokenKind::Minus) => BinaryOp::Sub,
+                _ => return Ok(expr),
             };
+            self.pos += 1;
+            let rhs = self.parse_multiplicative_expression()?;
+            expr = self.binary(op, expr, rhs);
+        }
+    }
 
-            self.buf = &buf_[(closing_quote_index + 1)..];
+    fn parse_multiplicative_expression(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_unary_expression()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Star) => BinaryOp::Mul,
+                Some(TokenKind::Slash) => BinaryOp::Div,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            let rhs = self.parse_unary_expression()?;
+            expr = self.binary(op, expr, rhs);
+        }
+    }
+
+    fn parse_unary_expression(&mut self) -> Result<Expr, Error> {
+        let op = match self.peek() {
+            Some(TokenKind::Plus) => UnaryOp::Plus,
+            Some(TokenKind::Minus) => UnaryOp::Minus,
+            Some(TokenKind::Bang) => UnaryOp::Not,
+            _ => return self.parse_power_expression(),
+        };
+        let start = self.peek_span().start;
+        self.pos += 1;
+        let operand = self.nested(Self::parse_unary_expression)?;
+        Ok(Expr {
+            kind: ExprKind::Unary(op, Box::new(operand)),
+            span: self.span_from(start),
+        })
+    }
 
-            Ok(s)
+    /// `^` is right-associative and binds tighter than the unary operators,
+    /// so that `-2^2 == -4` and `2^-1 == 1/2`
+    fn parse_power_expression(&mut self) -> Result<Expr, Error> {
+        let base = self.parse_funccall_or_decorated_block()?;
+        if self.peek() == Some(&TokenKind::Caret) {
+            self.pos += 1;
+            let exponent = self.nested(Self::parse_unary_expression)?;
+            Ok(self.binary(BinaryOp::Pow, base, exponent))
         } else {
-            Err(S("Not a string literal"))
-        }
-    }
-
-    fn parse_string_literal_and_load_single_file_dirty(&mut self) -> Result<Value, String> {
-        let filename = self.parse_string_literal()?;
-        println!("\x1b[2;34m##### Start of {filename}: \x1b[00m"); // faint blue
-
-        let mut f = std::fs::File::open(filename.clone()).map_err(|_| "File not found")?;
-        let mut content = String::new();
-        f.read_to_string(&mut content)
-            .map_err(|_| "something went wrong reading the file")?;
-
-        // boot up the new interpreter, inheriting the environment
-        let mut new_stack_trace = self.stack_trace.clone();
-        new_stack_trace.push(
-            filename
-                .strip_suffix(".periodicode")
-                .unwrap_or(&filename)
-                .to_owned(),
-        );
-
-        let mut new_ctx = Interpreter::new(
-            self.previous_value.clone(),
-            self.radix_context,
-            new_stack_trace,
-        );
-        let (value, radix_context) = new_ctx.execute_lines(&content)?;
-
-        self.previous_value = value.clone();
-
-        // write back the radix context
-        self.radix_context = radix_context;
-
-        println!("\x1b[2;34m##### End of {filename}\x1b[00m"); // faint blue
-
-        Ok(value)
-    }
-
-    fn parse_string_literal_and_load_single_file_clean(&mut self) -> Result<Value, String> {
-        let filename = self.parse_string_literal()?;
-        println!("\x1b[2;34m##### Entering {filename}: \x1b[00m"); // faint blue
-
-        let mut f = std::fs::File::open(filename.clone()).map_err(|_| "File not found")?;
-        let mut content = String::new();
-        f.read_to_string(&mut content)
-            .map_err(|_| "something went wrong reading the file")?;
-
-        // Boot up the interpreter with the default environment
-        // but keep track of the stack trace
-        let mut new_stack_trace = self.stack_trace.clone();
-        new_stack_trace.push(
-            filename
-                .strip_suffix(".periodicode")
-                .unwrap_or(&filename)
-                .to_owned(),
-        );
-        let mut new_ctx = Interpreter::new(BigRational::zero(), 10, new_stack_trace);
-
-        // Do not write back the radix context
-        let (value, _) = new_ctx.execute_lines(&content)?;
-
-        self.previous_value = value.clone();
-
-        println!("\x1b[2;34m##### Exiting {filename}\x1b[00m"); // faint blue
-
-        Ok(value)
-    }
-
-    fn parse_funccall_or_decorated_block(&mut self) -> Result<Value, String> {
-        self.trim_start();
-        if let Some(buf_) = self.buf.strip_prefix('@') {
-            self.buf = buf_.trim_start();
-            let ident = self.parse_identifier()?;
-            if let Some(new_radix_content) = ident.to_radix() {
-                let stashed_radix_content = self.radix_context;
-                self.radix_context = new_radix_content;
-                let val = self.parse_block_expression(Self::parse_expression)?;
-                self.radix_context = stashed_radix_content;
-                Ok(val)
-            } else if ident.0 == "load_dirty" {
-                self.parse_block_expression(Self::parse_string_literal_and_load_single_file_dirty)
-            } else if ident.0 == "load" {
-                self.parse_block_expression(Self::parse_string_literal_and_load_single_file_clean)
-            } else if ident.0 == "should_fail" {
-                self.consume_char_or_err(
-                    '(',
-                    "No parenthesis after the built-in function `should_fail`",
-                )?;
+            Ok(base)
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Result<(String, Span), Error> {
+        match self.peek() {
+            Some(TokenKind::Str(s)) => {
+                let s = s.clone();
+                Ok((s, self.next().expect("peeked").span))
+            }
+            _ => Err(self.error_here(ErrorKind::Syntax, "Not a string literal")),
+        }
+    }
 
-                let content = self.parse_string_literal()?;
+    fn parse_funccall_or_decorated_block(&mut self) -> Result<Expr, Error> {
+        let Some(TokenKind::At(name)) = self.peek() else {
+            return self.parse_primary_expression();
+        };
+        let name = name.clone();
+        let start = self.peek_span().start;
+        let name_span = self.peek_span();
+        self.pos += 1;
+
+        let kind = if let Some(radix) = self.parse_radix_after(&name)? {
+            ExprKind::RadixBlock(radix, self.parse_block(Self::parse_expression)?)
+        } else if let Some(builtin) = self.builtins.get(&name) {
+            self.parse_builtin_arguments(builtin.clone())?
+        } else {
+            match name.as_str() {
+                "if" => self.parse_if_arguments()?,
+                "repeat" | "while" => self.parse_loop_arguments(&name)?,
+                "fn" => self.parse_function_definition(name_span)?,
+                "else" => {
+                    return Err(Error::new(
+                        ErrorKind::Syntax,
+                        "`@else` must follow the closing `}` of an `@if` on the same line",
+                    )
+                    .with_span(name_span))
+                }
+                // a function defined by `@fn`, which is looked up when called
+                _ if self.peek() == Some(&TokenKind::LParen) => self.parse_call_arguments(name)?,
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::UnknownIdentifier,
+                        format!("UNSUPPORTED IDENTIFIER found after `@`: `@{name}`"),
+                    )
+                    .with_span(name_span))
+                }
+            }
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-                println!("\x1b[2;31m##### Start of ###should_fail###\x1b[00m",); // faint red
+    /// `@name(arg, ...)` with each argument parsed according to the kind declared by the built-in,
+    /// or `@name { ... }` when the built-in takes a block and nothing else is required
+    fn parse_builtin_arguments(&mut self, builtin: Builtin) -> Result<ExprKind, Error> {
+        let name = builtin.name();
+        let block_kind = match builtin.params().first() {
+            Some(&kind @ (ArgumentKind::Block | ArgumentKind::Strings))
+                if self.peek() == Some(&TokenKind::LBrace) && builtin.required() <= 1 =>
+            {
+                Some(kind)
+            }
+            _ => None,
+        };
+        if let Some(kind) = block_kind {
+            let block = self.parse_argument(name, kind)?;
+            return Ok(ExprKind::Builtin {
+                builtin,
+                args: vec![block],
+            });
+        }
 
-                // boot up the new interpreter, inheriting the environment
-                let mut new_stack_trace = self.stack_trace.clone();
-                new_stack_trace.push(S("\x1b[0;31m###should_fail###\x1b[00m"));
+        self.consume_or_err(
+            &TokenKind::LParen,
+            format!("No parenthesis after the built-in function `@{name}`"),
+        )?;
+        let mut args = vec![];
+        for (i, &kind) in builtin.params().iter().enumerate() {
+            if self.peek() == Some(&TokenKind::RParen) {
+                if i < builtin.required() {
+                    return Err(self.error_here(ErrorKind::Syntax, builtin.arity_message()));
+                }
+                break;
+            }
+            if i > 0 {
+                self.consume_or_err(&TokenKind::Comma, builtin.arity_message())?;
+            }
+            args.push(self.parse_argument(name, kind)?);
+        }
+        self.consume_or_err(&TokenKind::RParen, builtin.arity_message())?;
+        Ok(ExprKind::Builtin { builtin, args })
+    }
 
-                let mut new_ctx = Interpreter::new(
-                    self.previous_value.clone(),
-                    self.radix_context,
-                    new_stack_trace,
+    /// An argument of the built-in function `name` of the given kind
+    fn parse_argument(&mut self, name: &str, kind: ArgumentKind) -> Result<BuiltinArgument, Error> {
+        let argument = match (kind, self.peek()) {
+            (ArgumentKind::Expression, _) => {
+                return Ok(BuiltinArgument::Expression(self.parse_expression()?))
+            }
+            (ArgumentKind::String, Some(TokenKind::Str(_))) => {
+                Argument::String(self.parse_string_literal()?.0)
+            }
+            (ArgumentKind::Strings, Some(TokenKind::LBrace)) => {
+                Argument::Strings(self.parse_block(Self::parse_string_literal)?)
+            }
+            (ArgumentKind::Radix, Some(TokenKind::At(radix_name))) => {
+                let radix_name = radix_name.clone();
+                let error = self.error_here(
+                    ErrorKind::UnknownIdentifier,
+                    "Unrecognizable radix name found",
                 );
-                
-                let ans = match new_ctx.execute_lines(&content) {
-                    Err(msg) => {
-                        println!("\x1b[2;31m##### End of ###should_fail###\x1b[00m");
-                        println!("As expected, failure occurred: '\x1b[4m{msg}\x1b[00m'");
-
-                        // $_ is the result of a successful computation preceding the failure
-                        Ok(new_ctx.previous_value.clone())
-                    }
+                self.pos += 1;
+                Argument::Radix(self.parse_radix_after(&radix_name)?.ok_or(error)?)
+            }
+            (ArgumentKind::Block, Some(TokenKind::LBrace)) => {
+                let exprs = self.parse_block(Self::parse_expression)?;
+                Argument::Block(Block(BlockBody::Parsed(exprs.into())))
+            }
+            (ArgumentKind::Block, Some(TokenKind::Str(_))) => {
+                let source = self.parse_string_literal()?.0;
+                Argument::Block(Block(BlockBody::Source(source.into())))
+            }
+            (ArgumentKind::ExpectedFailure, Some(TokenKind::At(error_name))) => {
+                let error_kind = ErrorKind::from_name(error_name).ok_or_else(|| {
+                    self.error_here(
+                        ErrorKind::UnknownIdentifier,
+                        format!("`@{error_name}` is not a kind of error"),
+                    )
+                })?;
+                self.pos += 1;
+                Argument::ExpectedFailure(ExpectedFailure::Kind(error_kind))
+            }
+            (ArgumentKind::ExpectedFailure, Some(TokenKind::Str(_))) => {
+                Argument::ExpectedFailure(ExpectedFailure::Message(self.parse_string_literal()?.0))
+            }
+            _ => {
+                return Err(self.error_here(
+                    ErrorKind::Syntax,
+                    format!(
+                        "The built-in function `@{name}` expects {} here",
+                        kind.description()
+                    ),
+                ))
+            }
+        };
+        Ok(BuiltinArgument::Other(argument))
+    }
 
-                    Ok((value, _)) => {
-                        println!("\x1b[2;31m##### End of ###should_fail###\x1b[00m");
-                        Err(format!("\x1b[1;31m`@should_fail` DID NOT FAIL; it instead succeeded with the following value: {value}"))
-                    }
-                };
+    /// The radix denoted by `@name` and what follows it, which is a radix name such as `@hexadecimal`,
+    /// `@base N` or `@radix(N)` with `N` in decimal, or `None` for any other name
+    fn parse_radix_after(&mut self, name: &str) -> Result<Option<u32>, Error> {
+        let parenthesized = match name {
+            "base" => false,
+            "radix" => true,
+            _ => return Ok(radix_from_name(name)),
+        };
+        if parenthesized {
+            self.consume_or_err(&TokenKind::LParen, "No parenthesis after `@radix`")?;
+        }
+        let radix = match self.peek() {
+            Some(TokenKind::Number(literal)) => {
+                literal.parse::<u32>().ok().filter(|&radix| radix >= 2)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            self.error_here(
+                ErrorKind::Syntax,
+                format!("`@{name}` expects a radix of at least 2, written in decimal"),
+            )
+        })?;
+        self.pos += 1;
+        if parenthesized {
+            self.consume_or_err(
+                &TokenKind::RParen,
+                "Expected `)` after the radix of `@radix`",
+            )?;
+        }
+        Ok(Some(radix))
+    }
 
-                self.consume_char_or_err(
-                    ')',
-                    "The built-in function `should_fail` expects exactly one argument",
-                )?;
-                self.trim_start();
+    /// `@if (condition) { ... }`, optionally followed by `@else { ... }` or `@else @if ...`
+    fn parse_if_arguments(&mut self) -> Result<ExprKind, Error> {
+        self.consume_or_err(
+            &TokenKind::LParen,
+            "No parenthesis around the condition after `@if`",
+        )?;
+        let condition = self.parse_expression()?;
+        self.consume_or_err(
+            &TokenKind::RParen,
+            "Expected `)` after the condition of `@if`",
+        )?;
+        let then_branch = self.parse_block(Self::parse_expression)?;
+
+        let else_branch = match self.peek() {
+            Some(TokenKind::At(name)) if name == "else" => {
+                self.pos += 1;
+                if matches!(self.peek(), Some(TokenKind::At(name)) if name == "if") {
+                    Some(vec![self.nested(Self::parse_funccall_or_decorated_block)?])
+                } else {
+                    Some(self.parse_block(Self::parse_expression)?)
+                }
+            }
+            _ => None,
+        };
+        Ok(ExprKind::If {
+            condition: Box::new(condition),
+            then_branch,
+            else_branch,
+        })
+    }
 
-                ans
-            } else if ident.0 == "assert_eq" {
-                self.consume_char_or_err(
-                    '(',
-                    "No parenthesis after the built-in function `assert_eq`",
-                )?;
+    /// `@repeat(count) { ... }` or `@while (condition) { ... }`
+    fn parse_loop_arguments(&mut self, name: &str) -> Result<ExprKind, Error> {
+        self.consume_or_err(
+            &TokenKind::LParen,
+            format!("No parenthesis after `@{name}`"),
+        )?;
+        let arg = Box::new(self.parse_expression()?);
+        self.consume_or_err(
+            &TokenKind::RParen,
+            format!("`@{name}` expects exactly one argument"),
+        )?;
+        let body = self.parse_block(Self::parse_expression)?;
+        Ok(if name == "repeat" {
+            ExprKind::Repeat { count: arg, body }
+        } else {
+            ExprKind::While {
+                condition: arg,
+                body,
+            }
+        })
+    }
 
-                let first_arg = self.parse_expression()?;
-                self.consume_char_or_err(
-                    ',',
-                    "The built-in function `assert_eq` expects exactly two arguments",
-                )?;
-                let second_arg = self.parse_expression()?;
-                self.consume_char_or_err(
-                    ')',
-                    "The built-in function `assert_eq` expects exactly two arguments",
+    /// `@fn name($a, $b) { ... }`, where `fn_span` is that of `@fn`
+    fn parse_function_definition(&mut self, fn_span: Span) -> Result<ExprKind, Error> {
+        if self.in_function_body {
+            return Err(Error::new(
+                ErrorKind::Syntax,
+                "`@fn` cannot be used inside the body of another function",
+            )
+            .with_span(fn_span));
+        }
+        let Some(TokenKind::Identifier(name)) = self.peek() else {
+            return Err(self.error_here(
+                ErrorKind::Syntax,
+                "Expected the name of the function after `@fn`",
+            ));
+        };
+        let name = name.clone();
+        if is_keyword(&name) || self.builtins.get(&name).is_some() {
+            return Err(self.error_here(
+                ErrorKind::Syntax,
+                format!("`@{name}` is built in and cannot be redefined"),
+            ));
+        }
+        self.pos += 1;
+
+        self.consume_or_err(
+            &TokenKind::LParen,
+            format!("No parenthesis after the name of the function `{name}`"),
+        )?;
+        let mut params: Vec<String> = vec![];
+        while self.peek() != Some(&TokenKind::RParen) {
+            if !params.is_empty() {
+                self.consume_or_err(
+                    &TokenKind::Comma,
+                    format!("Expected `,` or `)` in the parameters of `@{name}`"),
                 )?;
-                self.trim_start();
-                if first_arg == second_arg {
-                    Ok(first_arg) // @assert_eq(7*6, 42) returns 42
-                } else {
-                    Err(format!(
-                        "ASSERTION FAILED: \nleft: {first_arg}\nright: {second_arg}"
+            }
+            let param = match self.peek() {
+                Some(TokenKind::Dollar(param)) if param == "_" => {
+                    return Err(self.error_here(ErrorKind::Syntax, "`$_` cannot be a parameter"))
+                }
+                Some(TokenKind::Dollar(param)) if params.contains(param) => {
+                    return Err(self.error_here(
+                        ErrorKind::Syntax,
+                        format!("The parameter `${param}` appears twice"),
                     ))
                 }
-            } else if ident.0 == "set_radix" {
-                self.consume_char_or_err(
-                    '(',
-                    "No parenthesis after the built-in function `set_radix`",
-                )?;
-                self.consume_char_or_err(
-                    '@',
-                    "No radix argument found in the built-in function `set_radix`",
-                )?;
-                self.trim_start();
-                let radix_ident = self.parse_identifier()?;
-
-                let radix: u32 = radix_ident
-                    .to_radix()
-                    .ok_or("Unrecognizable radix name found")?;
-
-                self.radix_context = radix;
+                Some(TokenKind::Dollar(param)) => param.clone(),
+                _ => {
+                    return Err(
+                        self.error_here(ErrorKind::Syntax, "Expected a parameter such as `$x`")
+                    )
+                }
+            };
+            params.push(param);
+            self.pos += 1;
+        }
+        self.pos += 1; // `)`
+
+        self.in_function_body = true;
+        let body = self.parse_block(Self::parse_expression);
+        self.in_function_body = false;
+
+        let source = self
+            .shared_source
+            .get_or_insert_with(|| Rc::from(self.source));
+        Ok(ExprKind::DefineFunction(Rc::new(Function {
+            name,
+            params,
+            body: body?,
+            source: Rc::clone(source),
+        })))
+    }
 
-                self.consume_char_or_err(
-                    ')',
-                    "The built-in function `set_radix` expects exactly one argument",
+    /// `(arg, ...)` after the name of a function defined by `@fn`
+    fn parse_call_arguments(&mut self, name: String) -> Result<ExprKind, Error> {
+        self.pos += 1; // `(`
+        let mut args = vec![];
+        while self.peek() != Some(&TokenKind::RParen) {
+            if !args.is_empty() {
+                self.consume_or_err(
+                    &TokenKind::Comma,
+                    format!("Expected `,` or `)` in the arguments of `@{name}`"),
                 )?;
-
-                Ok(BigRational::new(BigInt::from(radix), BigInt::one()))
-            } else {
-                Err(format!(
-                    "UNSUPPORTED IDENTIFIER found after `@`: `@{}`",
-                    ident.0
-                ))
             }
-        } else {
-            self.parse_primary_expression()
+            args.push(self.parse_expression()?);
         }
+        self.pos += 1; // `)`
+        Ok(ExprKind::Call { name, args })
     }
 
-    fn parse_block_expression<T>(&mut self, f: T) -> Result<Value, String>
+    /// Parses `{ item; item; ... }`, where `f` parses each item.
+    ///
+    /// Inside the block, it's allowed to have as many preceding or trailing semicolons,
+    /// but it must contain at least one item
+    fn parse_block<T, F>(&mut self, mut f: F) -> Result<Vec<T>, Error>
     where
-        T: Fn(&mut Self) -> Result<Value, String>,
+        F: FnMut(&mut Self) -> Result<T, Error>,
     {
-        self.trim_start();
-        self.consume_char_or_err('{', "Expected the start of a block")?;
+        self.consume_or_err(&TokenKind::LBrace, "Expected the start of a block")?;
 
-        // Inside the block, it's allowed to have as many preceding or trailing semicolons,
-        // but it must contain at least one expression
+        let mut items = vec![];
         loop {
-            match judge_termination_or_semicolons(self.buf, || ()) {
-                Judgement::EndOfLineEncountered => {
-                    return Err(S("Line is terminated but the block is unterminated"))
+            self.skip_semicolons();
+            match self.peek() {
+                None => return Err(self.error_here(ErrorKind::Syntax, "The block is unterminated")),
+                Some(TokenKind::RBrace) if !items.is_empty() => {
+                    self.pos += 1;
+                    return Ok(items);
                 }
-                Judgement::ExpressionTerminatedWithSemicolon(s) => self.buf = s,
-                Judgement::NoConsumption => {}
+                _ => {}
             }
 
-            let val = f(self)?;
-            self.trim_start();
-            match judge_termination_or_semicolons(self.buf, || ()) {
-                Judgement::NoConsumption => {
-                    self.consume_char_or_err('}', "Expected an operator or end of block")?;
-                    return Ok(val);
+            items.push(f(self)?);
+            match self.peek() {
+                Some(TokenKind::Semicolon) => {}
+                Some(TokenKind::RBrace) => {
+                    self.pos += 1;
+                    return Ok(items);
                 }
-                Judgement::EndOfLineEncountered => {
-                    return Err(S("Line is terminated but the block is unterminated"))
+                None => return Err(self.error_here(ErrorKind::Syntax, "The block is unterminated")),
+                Some(_) => {
+                    return Err(
+                        self.error_here(ErrorKind::Syntax, "Expected an operator or end of block")
+                    )
                 }
-                Judgement::ExpressionTerminatedWithSemicolon(s) => self.buf = s,
-            }
-            self.trim_start();
-            if let Some(buf_) = self.buf.strip_prefix('}') {
-                self.buf = buf_.trim_start();
-                return Ok(val);
             }
         }
     }
 
-    fn parse_primary_expression(&mut self) -> Result<Value, String> {
-        let buf = self.buf.trim_start();
-        if buf.starts_with('{') {
-            self.parse_block_expression(Self::parse_expression)
-        } else if let Some(buf) = buf.strip_prefix("$_") {
-            self.buf = buf;
-            Ok(self.previous_value.clone())
-        } else if let Some(buf_) = buf.strip_prefix('(') {
-            self.buf = buf_;
-            let value = self.parse_expression()?;
-            self.consume_char_or_err(')', "Mismatched parenthesis")?;
-            Ok(value)
-        } else if let Some(buf_) = buf.strip_prefix('[') {
-            self.buf = buf_;
-            let first_value = self.parse_expression()?;
-            let buf = self.buf.trim_start();
-            if let Some(buf_) = buf.strip_prefix(']') {
-                self.buf = buf_;
-                Ok(first_value)
-            } else if let Some(buf_) = buf.strip_prefix(';') {
-                self.buf = buf_;
-                // Currently forbid trailing commas
-                // what follows is (<value> <comma>)* <value> <]>
-                let mut values = vec![first_value];
-                loop {
-                    let val = self.parse_expression()?;
-                    values.push(val);
-                    let buf = self.buf.trim_start();
-                    if let Some(buf_) = buf.strip_prefix(',') {
-                        self.buf = buf_;
-                        continue;
-                    } else if let Some(buf_) = buf.strip_prefix(']') {
-                        self.buf = buf_;
-                        break;
+    fn parse_primary_expression(&mut self) -> Result<Expr, Error> {
+        let start = self.peek_span().start;
+        let kind = match self.peek() {
+            Some(TokenKind::LBrace) => ExprKind::Block(self.parse_block(Self::parse_expression)?),
+            Some(TokenKind::Dollar(name)) => {
+                let kind = if name == "_" {
+                    ExprKind::PreviousValue
+                } else {
+                    ExprKind::Variable(name.clone())
+                };
+                self.pos += 1;
+                kind
+            }
+            Some(TokenKind::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_expression()?;
+                self.consume_or_err(&TokenKind::RParen, "Mismatched parenthesis")?;
+                return Ok(Expr {
+                    kind: expr.kind,
+                    span: self.span_from(start),
+                });
+            }
+            Some(TokenKind::LBracket) => {
+                self.pos += 1;
+                self.parse_continued_fraction()?
+            }
+            Some(TokenKind::Number(literal)) => {
+                let mut literal = literal.clone();
+                self.pos += 1;
+                match self.signed_exponent_after(&literal) {
+                    Some(exponent) => {
+                        self.pos += 2;
+                        let sign = literal.len();
+                        literal.push_str(&exponent);
+                        ExprKind::SignedExponent { literal, sign }
                     }
+                    None => ExprKind::Literal(literal),
                 }
+            }
+            Some(TokenKind::Str(_)) => {
+                return Err(self.error_here(
+                    ErrorKind::Syntax,
+                    "A string literal cannot be used as a value",
+                ))
+            }
+            _ => return Err(self.error_here(ErrorKind::Syntax, "Expected an expression")),
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-                let final_result = values
-                    .into_iter()
-                    .rev()
-                    .reduce(|acc, e| acc.recip() + e)
-                    .expect("This iterator is not supposed to be empty");
-                Ok(final_result)
-            } else {
-                Err(S(
+    /// The signed exponent such as `-5` right after `literal`, which is a literal without a prefix ending with `e` as in `1e-5`
+    fn signed_exponent_after(&self, literal: &str) -> Option<String> {
+        if !literal.ends_with('e') || literal_own_radix(literal).is_some() {
+            return None;
+        }
+        let [literal_token, sign, exponent] = self.tokens.get(self.pos - 1..self.pos + 2)? else {
+            return None;
+        };
+        let sign_char = match sign.kind {
+            TokenKind::Plus => '+',
+            TokenKind::Minus => '-',
+            _ => return None,
+        };
+        match &exponent.kind {
+            TokenKind::Number(digits)
+                if literal_token.span.end == sign.span.start
+                    && sign.span.end == exponent.span.start =>
+            {
+                Some(format!("{sign_char}{digits}"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses what follows `[` in a continued-fraction literal
+    fn parse_continued_fraction(&mut self) -> Result<ExprKind, Error> {
+        if let Some(period) = self.parse_periodic_part()? {
+            // purely periodic, such as `[@rep{1}]`
+            self.consume_or_err(
+                &TokenKind::RBracket,
+                "`@rep{...}` must be the last slot of a continued-fraction literal",
+            )?;
+            return Ok(ExprKind::ContinuedFraction {
+                terms: vec![],
+                period: Some(period),
+            });
+        }
+
+        let mut terms = vec![self.parse_expression()?];
+        match self.next().map(|token| token.kind) {
+            Some(TokenKind::RBracket) => {
+                return Ok(ExprKind::ContinuedFraction {
+                    terms,
+                    period: None,
+                })
+            }
+            Some(TokenKind::Semicolon) => {}
+            _ => {
+                self.pos -= 1;
+                return Err(self.error_here(
+                    ErrorKind::Syntax,
                     "Expected `]` or `;` after the first slot of a continued-fraction literal",
-                ))
+                ));
+            }
+        }
+
+        // Currently forbid trailing commas
+        // what follows is (<value> <comma>)* <value> <]>
+        // or (<value> <comma>)* @rep{ (<value> <comma>)* <value> } <]>
+        loop {
+            if let Some(period) = self.parse_periodic_part()? {
+                self.consume_or_err(
+                    &TokenKind::RBracket,
+                    "`@rep{...}` must be the last slot of a continued-fraction literal",
+                )?;
+                return Ok(ExprKind::ContinuedFraction {
+                    terms,
+                    period: Some(period),
+                });
+            }
+            terms.push(self.parse_expression()?);
+            match self.peek() {
+                Some(TokenKind::Comma) => self.pos += 1,
+                Some(TokenKind::RBracket) => {
+                    self.pos += 1;
+                    return Ok(ExprKind::ContinuedFraction {
+                        terms,
+                        period: None,
+                    });
+                }
+                _ => {
+                    return Err(self.error_here(
+                        ErrorKind::Syntax,
+                        "Expected `,` or `]` inside a continued-fraction literal",
+                    ))
+                }
             }
-        } else {
-            let (value, remaining) =
-                numeric_literal::parse_numeric_literal_with_radix_context(buf, self.radix_context)?;
-            self.buf = remaining;
-            Ok(value)
         }
     }
 
-    fn parse_identifier(&mut self) -> Result<Identifier, String> {
-        static RE_IDENTIFIER: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"^[0-9a-zA-Z_]+").expect("regex compilation failed"));
+    /// Consumes `@rep{ <value>, <value>, ... }` if it comes next,
+    /// returning the repeated slots of a continued-fraction literal
+    fn parse_periodic_part(&mut self) -> Result<Option<Vec<Expr>>, Error> {
+        if !matches!(self.peek(), Some(TokenKind::At(name)) if name == "rep") {
+            return Ok(None);
+        }
+        self.pos += 1;
+        self.consume_or_err(&TokenKind::LBrace, "Expected `{` after `@rep`")?;
 
-        match RE_IDENTIFIER.captures(self.buf) {
-            None => Err(S("No identifier found after `@`")),
-            Some(u) => {
-                let whole = u.get(0).expect("regex match").as_str();
-                self.buf = self.buf.strip_prefix(whole).expect("regex match");
-                Ok(Identifier(String::from(whole)))
+        let mut values = vec![];
+        loop {
+            values.push(self.parse_expression()?);
+            if self.peek() == Some(&TokenKind::Comma) {
+                self.pos += 1;
+            } else {
+                self.consume_or_err(&TokenKind::RBrace, "Expected `,` or `}` inside `@rep{...}`")?;
+                return Ok(Some(values));
             }
         }
     }
 }
 
-impl Identifier {
-    fn to_radix(&self) -> Option<u32> {
-        let radix: u32 = match &self.0[..] {
-            "binary" => 2,
-            "trinary" | "ternary" => 3,
-            "quaternary" => 4,
-            "quinary" | "pental" => 5,
-            "senary" | "seximal" | "heximal" => 6,
-            "octal" | "oct" => 8,
-            "decimal" | "denary" | "decanary" | "dec" => 10,
-            "duodecimal" | "dozenal" => 12,
-            "hexadecimal" | "hex" => 16,
-            "vigesimal" => 20,
-            _ => return None,
-        };
-        Some(radix)
-    }
+/// Whether `@name` has a syntax of its own, so that it can name neither a built-in function nor a function defined by `@fn`
+pub(crate) fn is_keyword(name: &str) -> bool {
+    const KEYWORDS: [&str; 8] = [
+        "base", "radix", "if", "else", "repeat", "while", "fn", "rep",
+    ];
+    KEYWORDS.contains(&name) || radix_from_name(name).is_some()
+}
+
+/// Looks up a radix by the name that follows `@` in `@set_radix(@hexadecimal)`
+#[must_use]
+pub fn radix_from_name(name: &str) -> Option<u32> {
+    let radix: u32 = match name {
+        "binary" => 2,
+        "trinary" | "ternary" => 3,
+        "quaternary" => 4,
+        "quinary" | "pental" => 5,
+        "senary" | "seximal" | "heximal" => 6,
+        "octal" | "oct" => 8,
+        "decimal" | "denary" | "decanary" | "dec" => 10,
+        "duodecimal" | "dozenal" => 12,
+        "hexadecimal" | "hex" => 16,
+        "vigesimal" => 20,
+        "sexagesimal" | "babylonian" => 60,
+        _ => return None,
+    };
+    Some(radix)
 }
@@ -1,30 +1,98 @@
 use big_s::S;
 use num_bigint::BigInt;
 use num_bigint::BigUint;
-use num_bigint::ParseBigIntError;
 use num_rational::BigRational;
 use num_traits::identities::One;
 use num_traits::pow::Pow;
 use num_traits::Num;
 use num_traits::Zero;
-use once_cell::sync::Lazy;
 use regex::Regex;
+use std::sync::LazyLock;
 
+use crate::error::{Error, ErrorKind, Span};
 use crate::numerical_util::power;
 
-fn bigint_from_possibly_empty_str_radix(str: &str, radix: u32) -> Result<BigInt, ParseBigIntError> {
-    BigInt::from_str_radix(if str.is_empty() { "0" } else { str }, radix)
+/// The largest radix whose digits are written as single characters `0`-`9` and `a`-`z`;
+/// in a larger radix, each digit is written in decimal and the digits are separated by `:`, as in `1:30:00`
+pub const MAX_ALPHANUMERIC_RADIX: u32 = 36;
+
+/// The radix of a literal without a prefix whose digits are separated by `:`, such as `1:30:00`,
+/// when the radix context writes its digits as single characters
+pub const SEXAGESIMAL: u32 = 60;
+
+/// Whether `e` is a digit rather than an exponent marker in `radix`
+pub(crate) fn e_is_digit(radix: u32) -> bool {
+    (15..=MAX_ALPHANUMERIC_RADIX).contains(&radix)
 }
 
-fn map_parsebiginterror<T>(r: Result<T, ParseBigIntError>) -> Result<T, String> {
-    match r {
-        Ok(t) => Ok(t),
-        Err(e) => Err(e.to_string()),
+/// The radix given by the prefix of a literal such as `0x1e` or `36#ZZ`, or `None` when it is read in the radix context
+pub(crate) fn literal_own_radix(literal: &str) -> Option<u32> {
+    strip_radix_prefix(literal)
+        .ok()
+        .and_then(|(_, radix)| radix)
+}
+
+/// The value of the digits `str` in `radix`, which may be empty, together with the number of digits.
+///
+/// Beyond [`MAX_ALPHANUMERIC_RADIX`], the digits are separated by `:` or `,`,
+/// and the leading digit may exceed the radix if `leading_unbounded`, as the hours in `25:00:00` or the degrees in `123;30`.
+fn digits_from_possibly_empty_str_radix(
+    str: &str,
+    radix: u32,
+    leading_unbounded: bool,
+) -> Result<(BigInt, usize), String> {
+    if radix <= MAX_ALPHANUMERIC_RADIX {
+        let value = BigInt::from_str_radix(if str.is_empty() { "0" } else { str }, radix)
+            .map_err(|e| e.to_string())?;
+        return Ok((value, str.len()));
     }
+
+    let mut value = BigInt::zero();
+    let mut count = 0;
+    for digit in str.split([':', ',']).filter(|digit| !digit.is_empty()) {
+        let bounded = !(leading_unbounded && count == 0);
+        let digit: BigInt = digit
+            .parse()
+            .ok()
+            .filter(|digit| !bounded || *digit < BigInt::from(radix))
+            .ok_or_else(|| format!("invalid digit `{digit}` in radix {radix}"))?;
+        value = value * radix + digit;
+        count += 1;
+    }
+    Ok((value, count))
+}
+
+/// A signed integer such as the exponent in `1e-5`
+fn signed_integer_from_str_radix(str: &str, radix: u32) -> Result<BigInt, String> {
+    let (negative, digits) = match str.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, str.strip_prefix('+').unwrap_or(str)),
+    };
+    let (value, _) = digits_from_possibly_empty_str_radix(digits, radix, false)?;
+    Ok(if negative { -value } else { value })
 }
 
-fn strip_radix_prefix(input: &str) -> (&str, Option<u32>) {
-    if let Some(buf) = input.strip_prefix("0v") {
+/// `N#`, where `N` is a radix of at least 2 written in decimal, as in `36#ZZ` or `60#1:30`
+fn strip_numeric_radix_prefix(input: &str) -> Result<Option<(&str, u32)>, String> {
+    let Some((radix, rest)) = input.split_once('#') else {
+        return Ok(None);
+    };
+    if radix.is_empty() || !radix.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    match radix.parse::<u32>() {
+        Ok(radix) if radix >= 2 => Ok(Some((rest, radix))),
+        _ => Err(format!(
+            "invalid radix `{radix}` before `#`: expected a number of at least 2"
+        )),
+    }
+}
+
+fn strip_radix_prefix(input: &str) -> Result<(&str, Option<u32>), String> {
+    if let Some((buf, radix)) = strip_numeric_radix_prefix(input)? {
+        return Ok((buf, Some(radix)));
+    }
+    let prefixed = if let Some(buf) = input.strip_prefix("0v") {
         (buf, Some(20))
     } else if let Some(buf) = input.strip_prefix("0x") {
         (buf, Some(16))
@@ -46,15 +114,48 @@ fn strip_radix_prefix(input: &str) -> (&str, Option<u32>) {
         (buf, Some(2))
     } else {
         (input, None)
-    }
+    };
+    Ok(prefixed)
 }
 
+/// Parses a numeric literal at the beginning of `input`.
+///
+/// The span of an error is relative to `input` and covers the whole literal.
 pub fn parse_numeric_literal_with_radix_context(
     input: &str,
     radix_context: u32,
-) -> Result<(BigRational, &str), String> {
-    let (input, literal_own_radix) = strip_radix_prefix(input);
-    parse_numeric_literal_with_both_contexts(input, radix_context, literal_own_radix)
+) -> Result<(BigRational, &str), Error> {
+    strip_radix_prefix(input)
+        .and_then(|(stripped, literal_own_radix)| {
+            parse_numeric_literal_with_both_contexts(stripped, radix_context, literal_own_radix)
+        })
+        .map_err(|msg| {
+            let literal_len = input
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | ';' | '#')))
+                .unwrap_or(input.len());
+            let first_char_len = input.chars().next().map_or(0, char::len_utf8);
+            Error::new(ErrorKind::Syntax, msg)
+                .with_span(Span::new(0, literal_len.max(first_char_len)))
+        })
+}
+
+/// The beginning of a literal without a prefix that is sexagesimal by its digits separated by `:`, as `12:34:56.r7` is.
+///
+/// The Babylonian `;` is not part of it, as in `1:30;r20`, since that notation is only read
+/// behind `60#` or in a sexagesimal radix context, as the lexer does
+fn sexagesimal_by_separators(input: &str) -> Option<&str> {
+    let len = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | ':')))
+        .unwrap_or(input.len());
+    input[..len].contains(':').then_some(&input[..len])
+}
+
+/// `input` up to a `;` that is not followed by a digit or `r`, which is not a Babylonian radix point, as in `12;`
+fn without_bare_semicolon(input: &str) -> &str {
+    let bare = input
+        .match_indices(';')
+        .find(|(i, _)| !input[i + 1..].starts_with(|c: char| c.is_ascii_digit() || c == 'r'));
+    bare.map_or(input, |(i, _)| &input[..i])
 }
 
 fn parse_numeric_literal_with_both_contexts(
@@ -62,7 +163,7 @@ fn parse_numeric_literal_with_both_contexts(
     external_radix_context: u32,
     literal_own_radix: Option<u32>,
 ) -> Result<(BigRational, &str), String> {
-    /**
+    /*
      * exponent:
      * `e` or `xp`: multiplies the number by the power of the literal's own radix. `e` can only be used if the base is less than fifteen
      * `p`: multiplies the number by power of two.
@@ -71,25 +172,46 @@ fn parse_numeric_literal_with_both_contexts(
      *  - Hence, in decimal context, 0x1.0p10 == 1024.0
      */
 
-    static RE_ALLOWING_E: Lazy<Regex> = Lazy::new(|| {
-        Regex::new(r"^(?<integral>[0-9a-dA-D]*)(?<dot>\.(?<before_rep>[0-9a-dA-D]*)(?<rep_digits>(r[0-9a-dA-D]*)?))?(?<exponent>((e|xp|p)(\+|-)?[0-9a-dA-D]+)?)").expect("regex compilation failed")
+    static RE_ALLOWING_E: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<integral>[0-9a-dA-D]*)(?<dot>\.(?<before_rep>[0-9a-dA-D]*)(?<rep_digits>(r[0-9a-dA-D]+)?))?(?<exponent>((e|xp|p)(\+|-)?[0-9a-dA-D]+)?)").expect("regex compilation failed")
     });
 
-    static RE_FORBIDDING_E: Lazy<Regex> = Lazy::new(|| {
-        Regex::new(r"^(?<integral>[0-9a-oA-O]*)(?<dot>\.(?<before_rep>[0-9a-oA-O]*)(?<rep_digits>(r[0-9a-oA-O]*)?))?(?<exponent>((xp|p)(\+|-)?[0-9a-oA-O]+)?)").expect("regex compilation failed")
+    static RE_FORBIDDING_E: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<integral>[0-9a-oA-O]*)(?<dot>\.(?<before_rep>[0-9a-oA-O]*)(?<rep_digits>(r[0-9a-oA-O]+)?))?(?<exponent>((xp|p)(\+|-)?[0-9a-oA-O]+)?)").expect("regex compilation failed")
     });
 
-    let literal_own_radix = literal_own_radix.unwrap_or(external_radix_context);
+    /* Beyond radix 25, where `p` to `z` are digits as well, the letters that are digits must be in uppercase */
+    static RE_UPPERCASE_DIGITS: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<integral>[0-9a-oA-Z]*)(?<dot>\.(?<before_rep>[0-9a-oA-Z]*)(?<rep_digits>(r[0-9a-oA-Z]+)?))?(?<exponent>((xp|p)(\+|-)?[0-9a-oA-Z]+)?)").expect("regex compilation failed")
+    });
 
-    let caps = if literal_own_radix < 15 {
-        RE_ALLOWING_E
-            .captures(input)
-            .ok_or("No parse as a numeric literal")?
-    } else {
-        RE_FORBIDDING_E
-            .captures(input)
-            .ok_or("No parse as a numeric literal")?
+    /*
+     * Beyond radix 36, the digits are written in decimal and separated by `:`, as in `1:30.r20`,
+     * or in the Babylonian notation with `;` as the radix point and `,` separating the fractional digits, as in `1;24,51,10`
+     */
+    static RE_SEPARATED_DIGITS: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<integral>([0-9]+(:[0-9]+)*)?)(?<dot>[.;](?<before_rep>([0-9]+([:,][0-9]+)*)?)[:,]?(?<rep_digits>(r[0-9]+([:,][0-9]+)*)?))?(?<exponent>((e|xp|p)(\+|-)?[0-9]+(:[0-9]+)*)?)").expect("regex compilation failed")
+    });
+
+    let (literal_own_radix, digits) = match literal_own_radix {
+        Some(radix) => (radix, input),
+        None => match sexagesimal_by_separators(input) {
+            Some(digits) if external_radix_context <= MAX_ALPHANUMERIC_RADIX => {
+                (SEXAGESIMAL, digits)
+            }
+            _ => (external_radix_context, input),
+        },
+    };
+
+    let regex = match literal_own_radix {
+        ..15 => &RE_ALLOWING_E,
+        15..=25 => &RE_FORBIDDING_E,
+        26..=MAX_ALPHANUMERIC_RADIX => &RE_UPPERCASE_DIGITS,
+        _ => &RE_SEPARATED_DIGITS,
     };
+    let caps = regex
+        .captures(without_bare_semicolon(digits))
+        .ok_or("No parse as a numeric literal")?;
 
     let whole = caps.get(0).expect("regex match").as_str();
     if whole.is_empty() {
@@ -99,7 +221,7 @@ fn parse_numeric_literal_with_both_contexts(
     let integral = caps.name("integral").expect("regex match").as_str();
     let (before_rep, repeating_digits) = match caps.name("dot") {
         Some(u) => {
-            if u.as_str() == "." && integral.is_empty() {
+            if matches!(u.as_str(), "." | ";") && integral.is_empty() {
                 return Err(S(
                     "\"A standalone single dot `.`, optionally followed by exponent\" is forbidden",
                 ));
@@ -115,54 +237,39 @@ fn parse_numeric_literal_with_both_contexts(
 
     let exponent = caps.name("exponent").expect("regex match").as_str();
 
-    let integral_part: BigRational = BigRational::new(
-        map_parsebiginterror(bigint_from_possibly_empty_str_radix(
-            integral,
-            literal_own_radix,
-        ))?,
-        BigInt::one(),
+    let integral_part: BigRational = BigRational::from_integer(
+        digits_from_possibly_empty_str_radix(integral, literal_own_radix, true)?.0,
     );
 
-    let scaling = BigInt::from(literal_own_radix).pow(BigUint::from(before_rep.len()));
+    let (before_rep_digits, before_rep_len) =
+        digits_from_possibly_empty_str_radix(before_rep, literal_own_radix, false)?;
+    let scaling = BigInt::from(literal_own_radix).pow(BigUint::from(before_rep_len));
 
-    let before_rep_part: BigRational = BigRational::new(
-        map_parsebiginterror(bigint_from_possibly_empty_str_radix(
-            before_rep,
-            literal_own_radix,
-        ))?,
-        scaling.clone(),
-    );
+    let before_rep_part: BigRational = BigRational::new(before_rep_digits, scaling.clone());
 
-    let repeating_digits_part: BigRational = if let Some(true_digits) =
-        repeating_digits.strip_prefix('r')
-    {
-        BigRational::new(
-            map_parsebiginterror(bigint_from_possibly_empty_str_radix(
-                true_digits,
-                literal_own_radix,
-            ))?,
-            scaling
-                * (BigInt::from(literal_own_radix).pow(BigUint::from(repeating_digits.len() - 1))
-                    - BigInt::one()),
-        )
-    } else {
-        BigRational::zero()
-    };
+    let repeating_digits_part: BigRational =
+        if let Some(true_digits) = repeating_digits.strip_prefix('r') {
+            let (digits, len) =
+                digits_from_possibly_empty_str_radix(true_digits, literal_own_radix, false)?;
+            BigRational::new(
+                digits,
+                scaling * (BigInt::from(literal_own_radix).pow(BigUint::from(len)) - BigInt::one()),
+            )
+        } else {
+            BigRational::zero()
+        };
 
     /* what follows the `e`, `p` or `xp` is interpreted using the external context */
     let exponent: BigRational = if let Some(true_digits) = exponent.strip_prefix('e') {
         // power of radix
-        let exponent =
-            map_parsebiginterror(BigInt::from_str_radix(true_digits, external_radix_context))?;
+        let exponent = signed_integer_from_str_radix(true_digits, external_radix_context)?;
         power(literal_own_radix, exponent)
     } else if let Some(true_digits) = exponent.strip_prefix("xp") {
-        let exponent =
-            map_parsebiginterror(BigInt::from_str_radix(true_digits, external_radix_context))?;
+        let exponent = signed_integer_from_str_radix(true_digits, external_radix_context)?;
         power(literal_own_radix, exponent)
     } else if let Some(true_digits) = exponent.strip_prefix('p') {
         // power of 2
-        let exponent =
-            map_parsebiginterror(BigInt::from_str_radix(true_digits, external_radix_context))?;
+        let exponent = signed_integer_from_str_radix(true_digits, external_radix_context)?;
         power(2, exponent)
     } else {
         BigRational::one()
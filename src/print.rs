@@ -1,119 +1,521 @@
+use std::fmt::{self, Write as _};
+
+use big_s::S;
+
 use crate::numerical_util::floor_as_bigint;
 use num_bigint::BigInt;
+use num_integer::Integer;
 use num_rational::BigRational;
 use num_traits::identities::One;
-use num_traits::Zero;
-
-pub fn rational_print_summary(ans: &BigRational, external_radix_context: u32) {
-    let numer = ans.numer();
-    let denom = ans.denom();
+use num_traits::{Signed, ToPrimitive, Zero};
 
-    print!("frac: ");
+use crate::number_theory::expansion_lengths;
+use crate::parse::numeric_literal::{MAX_ALPHANUMERIC_RADIX, SEXAGESIMAL};
+use crate::quadratic::QuadraticIrrational;
+use crate::value::Value;
 
-    /* print fractional */
-    if denom == &BigInt::one() {
-        print!("{}", numer.to_str_radix(external_radix_context));
-    } else {
-        print!(
-            "{}/{}",
-            numer.to_str_radix(external_radix_context),
-            denom.to_str_radix(external_radix_context)
-        );
+/// Writes the summary of `ans` in the given radix, one line for each representation:
+/// `frac:` and `cont:`, followed by `digt:` for a rational.
+/// In radix 60, a rational also gets `baby:` in the Babylonian notation,
+/// `time:` as hours, minutes and seconds of `ans` seconds, and `angl:` as degrees, arcminutes and arcseconds of `ans` degrees.
+///
+/// Outside radix 10, each of the first three lines is followed by the decimal equivalent as a comment,
+/// which is shown in faint green when `colored` is true.
+/// A period longer than `max_period_digits` is elided in the middle.
+///
+/// # Errors
+///
+/// Fails only when `out` does.
+pub fn write_summary(
+    out: &mut impl fmt::Write,
+    ans: &Value,
+    external_radix_context: u32,
+    max_period_digits: usize,
+    colored: bool,
+) -> fmt::Result {
+    let mut line = |label: &str, format: &dyn Fn(u32) -> String| {
+        write_summary_line(out, label, &format, external_radix_context, colored)
+    };
+    match ans {
+        Value::Rational(ans) => {
+            line("frac", &|radix| format_fraction(ans, radix))?;
+            line("cont", &|radix| format_continued_fraction(ans, radix))?;
+            line("digt", &|radix| {
+                format_digit_expansion(ans, radix, max_period_digits)
+            })?;
+            if external_radix_context == SEXAGESIMAL {
+                writeln!(
+                    out,
+                    "baby: {}",
+                    format_babylonian_expansion(ans, max_period_digits)
+                )?;
+                writeln!(
+                    out,
+                    "time: {}",
+                    format_hours_minutes_seconds(ans, max_period_digits)
+                )?;
+                writeln!(
+                    out,
+                    "angl: {}",
+                    format_degrees_minutes_seconds(ans, max_period_digits)
+                )?;
+            }
+            Ok(())
+        }
+        Value::Quadratic(ans) => {
+            line("frac", &|radix| ans.to_str_radix(radix))?;
+            line("cont", &|radix| {
+                format_periodic_continued_fraction(ans, radix)
+            })
+        }
     }
+}
+
+fn write_summary_line(
+    out: &mut impl fmt::Write,
+    label: &str,
+    format: &dyn Fn(u32) -> String,
+    external_radix_context: u32,
+    colored: bool,
+) -> fmt::Result {
+    write!(out, "{label}: {}", format(external_radix_context))?;
     if external_radix_context != 10 {
-        print!(" \x1b[2;32m# @decimal {{ {ans} }}\x1b[00m"); // faint green
+        let comment = format!("# @decimal {{ {} }}", format(10));
+        if colored {
+            write!(out, " \x1b[2;32m{comment}\x1b[00m")?; // faint green
+        } else {
+            write!(out, " {comment}")?;
+        }
     }
+    writeln!(out)
+}
 
-    println!();
+/// The summary written by [`write_summary`], as a string
+#[must_use]
+pub fn format_summary(
+    ans: &Value,
+    external_radix_context: u32,
+    max_period_digits: usize,
+    colored: bool,
+) -> String {
+    let mut summary = String::new();
+    // writing to a String never fails
+    let _ = write_summary(
+        &mut summary,
+        ans,
+        external_radix_context,
+        max_period_digits,
+        colored,
+    );
+    summary
+}
 
-    print!("cont: ");
+/// A JSON string literal
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                // writing to a String never fails
+                let _ = write!(escaped, "\\u{:04x}", u32::from(c));
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
 
-    print_continued_fraction_radix(ans, external_radix_context);
+/// A JSON array of strings
+fn json_string_array(items: impl IntoIterator<Item = String>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| json_string(&item)).collect();
+    format!("[{}]", items.join(","))
+}
 
-    if external_radix_context != 10 {
-        print!(" \x1b[2;32m# @decimal {{ "); // faint green
-        print_continued_fraction_radix(ans, 10);
-        print!(" }}\x1b[00m"); // reset
+/// A single-line JSON object describing `ans`, the result of the code at `line:column` of `file`.
+///
+/// Every number is given as a string in `external_radix_context`, so that large numbers keep their precision:
+///
+/// - a rational has the fields `numerator`, `denominator`, `continued_fraction`,
+///   `negative`, `integral_digits`, `non_repeating_digits`, `repeating_digits`, `repeating_digits_tail`,
+///   `elided` and `period_length`, where `period_length` is a JSON number in decimal.
+///   When the period is longer than `max_period_digits`, `elided` is true and only its first digits are in `repeating_digits`
+///   and its last digits in `repeating_digits_tail`; otherwise, `repeating_digits_tail` is empty
+/// - a quadratic irrational has the fields `closed_form`, `continued_fraction` and `continued_fraction_period`,
+///   the last of which is `null` when the period is too long to be found
+#[must_use]
+pub fn format_json_summary(
+    ans: &Value,
+    external_radix_context: u32,
+    max_period_digits: usize,
+    file: &str,
+    line: usize,
+    column: usize,
+) -> String {
+    let mut fields = vec![
+        format!("\"file\":{}", json_string(file)),
+        format!("\"line\":{line}"),
+        format!("\"column\":{column}"),
+        format!("\"radix\":{external_radix_context}"),
+    ];
+    let to_str = |n: &BigInt| format_integer(n, external_radix_context);
+
+    match ans {
+        Value::Rational(ans) => {
+            let expansion = digit_expansion(ans, external_radix_context, max_period_digits);
+            fields.extend([
+                format!("\"type\":{}", json_string("rational")),
+                format!("\"numerator\":{}", json_string(&to_str(ans.numer()))),
+                format!("\"denominator\":{}", json_string(&to_str(ans.denom()))),
+                format!(
+                    "\"continued_fraction\":{}",
+                    json_string_array(FiniteContinuedFractionIter::new(ans).map(|n| to_str(&n)))
+                ),
+                format!("\"negative\":{}", expansion.negative),
+                format!("\"integral_digits\":{}", json_string(&expansion.integral)),
+                format!(
+                    "\"non_repeating_digits\":{}",
+                    json_string(&expansion.non_repeating)
+                ),
+                format!("\"repeating_digits\":{}", json_string(&expansion.repeating)),
+                format!(
+                    "\"repeating_digits_tail\":{}",
+                    json_string(expansion.repeating_tail.as_deref().unwrap_or_default())
+                ),
+                format!("\"elided\":{}", expansion.repeating_tail.is_some()),
+                format!("\"period_length\":{}", expansion.period),
+            ]);
+        }
+        Value::Quadratic(ans) => {
+            let (mut terms, period_start) = ans.continued_fraction(MAX_CONTINUED_FRACTION_TERMS);
+            let period = period_start.map(|start| terms.split_off(start));
+            fields.extend([
+                format!("\"type\":{}", json_string("quadratic_irrational")),
+                format!(
+                    "\"closed_form\":{}",
+                    json_string(&ans.to_str_radix(external_radix_context))
+                ),
+                format!(
+                    "\"continued_fraction\":{}",
+                    json_string_array(terms.iter().map(to_str))
+                ),
+                format!(
+                    "\"continued_fraction_period\":{}",
+                    period.map_or(S("null"), |period| json_string_array(
+                        period.iter().map(to_str)
+                    ))
+                ),
+            ]);
+        }
     }
 
-    println!();
+    format!("{{{}}}", fields.join(","))
+}
 
-    print!("digt: ");
+/// The number of terms up to which the period of a continued fraction is searched for
+const MAX_CONTINUED_FRACTION_TERMS: usize = 1000;
 
-    print_digit_expansion_radix(ans, external_radix_context);
+/// The continued fraction of a quadratic irrational such as `[1; @rep{1, 2}]`,
+/// which is cut off with `...` when the period is too long
+#[must_use]
+pub fn format_periodic_continued_fraction(
+    ans: &QuadraticIrrational,
+    external_radix_context: u32,
+) -> String {
+    let (terms, period_start) = ans.continued_fraction(MAX_CONTINUED_FRACTION_TERMS);
+    let mut terms: Vec<String> = terms
+        .into_iter()
+        .map(|n| format_integer(&n, external_radix_context))
+        .collect();
 
-    if external_radix_context != 10 {
-        print!(" \x1b[2;32m# @decimal {{ "); // faint green
-        print_digit_expansion_radix(ans, 10);
-        print!(" }}\x1b[00m"); // reset
+    // The leading slot is always written outside `@rep`, so that the output can be read back in;
+    // a purely periodic expansion is rotated by one slot
+    match period_start {
+        Some(0) => {
+            let first = terms[0].clone();
+            terms.push(first);
+            format!("[{}; @rep{{{}}}]", terms[0], terms[1..].join(", "))
+        }
+        Some(start) => {
+            let (non_periodic, periodic) = terms.split_at(start);
+            let mut slots = non_periodic[1..].to_vec();
+            slots.push(format!("@rep{{{}}}", periodic.join(", ")));
+            format!("[{}; {}]", non_periodic[0], slots.join(", "))
+        }
+        None => format!("[{}; {}, ...]", terms[0], terms[1..].join(", ")),
     }
-
-    println!();
 }
 
-fn print_continued_fraction_radix(ans: &BigRational, external_radix_context: u32) {
-    let mut cont_frac_iter = FiniteContinuedFractionIter::new(ans);
-    let initial = cont_frac_iter.next().expect("empty iterator");
-    let remaining: Vec<BigInt> = cont_frac_iter.collect();
-    if remaining.is_empty() {
-        print!("[{}]", initial.to_str_radix(external_radix_context));
+/// A single digit in `radix`, which is a letter in uppercase beyond radix 25 so that `p` and `r` are read back as digits,
+/// and a decimal number padded with zeros to a fixed width beyond radix 36, as in the `05` of `1:05`
+fn format_digit(digit: u32, radix: u32) -> String {
+    if radix > MAX_ALPHANUMERIC_RADIX {
+        let width = (radix - 1).to_string().len();
+        return format!("{digit:0width$}");
+    }
+    let digit = char::from_digit(digit, radix).unwrap_or('?');
+    if radix > 25 {
+        digit.to_ascii_uppercase().to_string()
     } else {
-        print!(
-            "[{}; {}]",
-            initial.to_str_radix(external_radix_context),
-            remaining
-                .into_iter()
-                .map(|n| n.to_str_radix(external_radix_context))
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
+        digit.to_string()
     }
 }
 
-fn print_digit_expansion_radix(ans: &BigRational, external_radix_context: u32) {
-    if ans < &BigRational::zero() {
-        print!("-");
-        print_digit_expansion_radix(&-ans, external_radix_context);
-        return;
+/// What is written between two digits, which is `:` beyond radix 36
+fn digit_separator(radix: u32) -> &'static str {
+    if radix > MAX_ALPHANUMERIC_RADIX {
+        ":"
+    } else {
+        ""
     }
+}
 
-    print!(
-        "{}",
-        floor_as_bigint(ans).to_str_radix(external_radix_context)
-    );
+/// An integer written in `radix`, as in `ff`, `ZZ` in radix 36 or `1:30:00` in radix 60
+pub(crate) fn format_integer(n: &BigInt, radix: u32) -> String {
+    if radix <= 25 {
+        return n.to_str_radix(radix);
+    }
+    if radix <= MAX_ALPHANUMERIC_RADIX {
+        return n.to_str_radix(radix).to_ascii_uppercase();
+    }
+    let mut magnitude = n.abs();
+    let mut digits = vec![];
+    while !magnitude.is_zero() {
+        let (quotient, digit) = magnitude.div_rem(&BigInt::from(radix));
+        digits.push(digit.to_u32().unwrap_or_default());
+        magnitude = quotient;
+    }
+    let mut s = String::from(if n.is_negative() { "-" } else { "" });
+    match digits.split_last() {
+        Some((leading, rest)) => {
+            s.push_str(&leading.to_string());
+            for &digit in rest.iter().rev() {
+                s.push(':');
+                s.push_str(&format_digit(digit, radix));
+            }
+        }
+        None => s.push('0'),
+    }
+    s
+}
 
-    let mut f = ans - ans.floor();
-    if f.is_zero() {
-        return;
+/// The fraction in lowest terms such as `-7/3`, or just the numerator for an integer
+#[must_use]
+pub fn format_fraction(ans: &BigRational, external_radix_context: u32) -> String {
+    let numer = format_integer(ans.numer(), external_radix_context);
+    if ans.denom() == &BigInt::one() {
+        numer
+    } else {
+        format!(
+            "{numer}/{}",
+            format_integer(ans.denom(), external_radix_context)
+        )
     }
+}
 
-    print!(".");
+/// The finite continued fraction such as `[3; 7, 16]`
+#[must_use]
+pub fn format_continued_fraction(ans: &BigRational, external_radix_context: u32) -> String {
+    let terms: Vec<String> = FiniteContinuedFractionIter::new(ans)
+        .map(|n| format_integer(&n, external_radix_context))
+        .collect();
+    match terms.split_first() {
+        Some((initial, remaining)) if !remaining.is_empty() => {
+            format!("[{initial}; {}]", remaining.join(", "))
+        }
+        _ => format!("[{}]", terms.concat()),
+    }
+}
 
-    let mut f_list = vec![];
-    let mut digits = vec![];
+/// The number of repeating digits shown by default; a longer period is elided in the middle as in `r142…857 (period 1000002)`
+pub const DEFAULT_MAX_PERIOD_DIGITS: usize = 100;
 
-    loop {
-        f_list.push(f.clone());
+/// The positional expansion of a rational, split at the radix point and at the start of the repeating digits
+struct DigitExpansion {
+    negative: bool,
+    integral: String,
+    non_repeating: String,
 
-        f *= BigInt::from(external_radix_context);
+    /// The whole period, or its first digits when it is longer than the limit
+    repeating: String,
 
-        let digit = floor_as_bigint(&f).to_str_radix(external_radix_context);
-        digits.push(digit.clone());
+    /// The last digits of a period longer than the limit, which are shown after `…`
+    repeating_tail: Option<String>,
+    period: BigInt,
+}
 
-        f = f.clone() - f.floor();
-        if f.is_zero() {
-            print!("{}", digits.join(""));
-            return;
+impl DigitExpansion {
+    /// The repeating digits, elided in the middle as in `142…857 (period 1000002)` when there are too many of them
+    fn repeating_digits(&self) -> String {
+        match &self.repeating_tail {
+            Some(tail) => format!("{}…{tail} (period {})", self.repeating, self.period),
+            None => self.repeating.clone(),
         }
+    }
+}
 
-        if f_list.contains(&f) {
-            let pos = f_list.iter().position(|k| k == &f).expect("empty iterator");
-            print!("{}r{}", digits[0..pos].join(""), digits[pos..].join(""));
-            return;
+/// The next `count` digits of `remainder / denominator` in radix `radix`, leaving the remainder after them
+fn long_division(remainder: &mut BigInt, denominator: &BigInt, radix: u32, count: usize) -> String {
+    let digits: Vec<String> = (0..count)
+        .map(|_| {
+            *remainder *= radix;
+            let (digit, rest) = remainder.div_rem(denominator);
+            *remainder = rest;
+            // each digit is less than the radix
+            format_digit(digit.to_u32().unwrap_or_default(), radix)
+        })
+        .collect();
+    digits.join(digit_separator(radix))
+}
+
+/// The expansion, where only the first and last digits of a period longer than `max_period_digits` are computed.
+///
+/// The lengths of the pre-period and the period come from the factors of the denominator,
+/// so that no remainder has to be remembered, and the last digits of a long period
+/// start from a remainder found by modular exponentiation.
+fn digit_expansion(
+    ans: &BigRational,
+    external_radix_context: u32,
+    max_period_digits: usize,
+) -> DigitExpansion {
+    let radix = external_radix_context;
+    let negative = ans < &BigRational::zero();
+    let ans = ans.abs();
+    let integral = format_integer(&floor_as_bigint(&ans), radix);
+
+    let denominator = ans.denom();
+    let (preperiod, period) = expansion_lengths(denominator, radix);
+    let mut remainder = ans.numer() % denominator;
+    let preperiod = usize::try_from(preperiod).unwrap_or(usize::MAX);
+    let non_repeating = long_division(&mut remainder, denominator, radix, preperiod);
+
+    let (repeating, repeating_tail) = match period.to_usize() {
+        Some(length) if length <= max_period_digits => (
+            long_division(&mut remainder, denominator, radix, length),
+            None,
+        ),
+        _ => {
+            let tail_length = max_period_digits / 2;
+            let head_length = max_period_digits - tail_length;
+            let mut tail_remainder = &remainder
+                * BigInt::from(radix).modpow(&(&period - tail_length), denominator)
+                % denominator;
+            (
+                long_division(&mut remainder, denominator, radix, head_length),
+                Some(long_division(
+                    &mut tail_remainder,
+                    denominator,
+                    radix,
+                    tail_length,
+                )),
+            )
         }
+    };
+
+    DigitExpansion {
+        negative,
+        integral,
+        non_repeating,
+        repeating,
+        repeating_tail,
+        period,
     }
 }
 
+/// The positional expansion, where `r` marks the start of the repeating digits as in `0.1r6`.
+/// A period longer than `max_period_digits` is shown as its first and last digits followed by its length,
+/// as in `0.r142…857 (period 1000002)`.
+#[must_use]
+pub fn format_digit_expansion(
+    ans: &BigRational,
+    external_radix_context: u32,
+    max_period_digits: usize,
+) -> String {
+    let expansion = digit_expansion(ans, external_radix_context, max_period_digits);
+    let mut s = String::new();
+    if expansion.negative {
+        s.push('-');
+    }
+    s.push_str(&expansion.integral);
+    if !expansion.non_repeating.is_empty() || !expansion.period.is_zero() {
+        s.push('.');
+        s.push_str(&expansion.non_repeating);
+    }
+    if !expansion.period.is_zero() {
+        s.push('r');
+        s.push_str(&expansion.repeating_digits());
+    }
+    s
+}
+
+/// The sexagesimal expansion in the Babylonian notation, where `;` is the radix point and `,` separates the fractional places,
+/// as in `0;r08,34,17` for 1/7 or `1:40:00:00` for 360000
+#[must_use]
+pub fn format_babylonian_expansion(ans: &BigRational, max_period_digits: usize) -> String {
+    let expansion = digit_expansion(ans, SEXAGESIMAL, max_period_digits);
+    // the fractional places come joined by `:`
+    let places = |digits: &str| digits.replace(':', ",");
+    let mut s = String::new();
+    if expansion.negative {
+        s.push('-');
+    }
+    s.push_str(&expansion.integral);
+    if !expansion.non_repeating.is_empty() || !expansion.period.is_zero() {
+        s.push(';');
+        s.push_str(&places(&expansion.non_repeating));
+    }
+    if !expansion.period.is_zero() {
+        s.push('r');
+        s.push_str(&places(&expansion.repeating_digits()));
+    }
+    s
+}
+
+/// `seconds` as a duration such as `100:00:00`, where the hours may exceed 23
+/// and the fraction of a second is written in decimal as in `0:00:01.r3`
+#[must_use]
+pub fn format_hours_minutes_seconds(seconds: &BigRational, max_period_digits: usize) -> String {
+    format_sexagesimal_units(seconds, [":", ":", ""], max_period_digits)
+}
+
+/// `degrees` as an angle such as `123°30′00″`, where the degrees may exceed 359
+/// and the fraction of an arcsecond is written in decimal as in `0°00′01.5″`
+#[must_use]
+pub fn format_degrees_minutes_seconds(degrees: &BigRational, max_period_digits: usize) -> String {
+    let arcseconds = degrees * BigRational::from_integer(3600.into());
+    format_sexagesimal_units(&arcseconds, ["°", "′", "″"], max_period_digits)
+}
+
+/// The leading unit, then the two places of 60 below it, each followed by its mark in `marks`,
+/// given the value in the units of the last place
+fn format_sexagesimal_units(
+    ans: &BigRational,
+    marks: [&str; 3],
+    max_period_digits: usize,
+) -> String {
+    let sixty = BigInt::from(SEXAGESIMAL);
+    let magnitude = ans.abs();
+    let whole = floor_as_bigint(&magnitude);
+    let (minutes, _) = whole.div_rem(&sixty);
+    let (leading, minute) = minutes.div_rem(&sixty);
+    let last = magnitude - BigRational::from_integer(&minutes * &sixty);
+    // the last place is written with two digits before its fraction, as the minutes are
+    let padding = if last < BigRational::from_integer(10.into()) {
+        "0"
+    } else {
+        ""
+    };
+    let last = format_digit_expansion(&last, 10, max_period_digits);
+    let sign = if ans.is_negative() { "-" } else { "" };
+    let [leading_mark, minute_mark, last_mark] = marks;
+    format!("{sign}{leading}{leading_mark}{minute:02}{minute_mark}{padding}{last}{last_mark}")
+}
+
 enum FiniteContinuedFractionIter {
     Ratio(BigRational),
     Infinity,
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, Zero};
+
+use crate::error::{Error, ErrorKind};
+use crate::number_theory::factorize_with_effort;
+use crate::print::format_integer;
+use crate::value::Value;
+
+/// A quadratic irrational `r + s√d`,
+/// where `r` and `s` are rationals with `s != 0` and `d > 1` is a square-free integer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuadraticIrrational {
+    rational_part: BigRational,
+    irrational_coefficient: BigRational,
+    radicand: BigInt,
+}
+
+impl QuadraticIrrational {
+    /// `r + s√d`, which collapses into a rational when `s == 0`
+    ///
+    /// `d` must be a square-free integer greater than 1
+    #[must_use]
+    pub fn new_value(r: BigRational, s: BigRational, d: BigInt) -> Value {
+        if s.is_zero() {
+            Value::Rational(r)
+        } else {
+            Value::Quadratic(Self {
+                rational_part: r,
+                irrational_coefficient: s,
+                radicand: d,
+            })
+        }
+    }
+
+    #[must_use]
+    pub fn rational_part(&self) -> &BigRational {
+        &self.rational_part
+    }
+
+    #[must_use]
+    pub fn irrational_coefficient(&self) -> &BigRational {
+        &self.irrational_coefficient
+    }
+
+    #[must_use]
+    pub fn radicand(&self) -> &BigInt {
+        &self.radicand
+    }
+
+    /// Returns `(a, b, c)` such that the value equals `(a + b√d) / c` with `c > 0` and `gcd(a, b, c) == 1`
+    #[must_use]
+    pub fn closed_form(&self) -> (BigInt, BigInt, BigInt) {
+        let c = self
+            .rational_part
+            .denom()
+            .lcm(self.irrational_coefficient.denom());
+        let a = self.rational_part.numer() * (&c / self.rational_part.denom());
+        let b = self.irrational_coefficient.numer() * (&c / self.irrational_coefficient.denom());
+        let g = a.gcd(&b).gcd(&c);
+        (a / &g, b / &g, c / &g)
+    }
+
+    /// The closed form `(a + b√d)/c`, with each integer written in the given radix
+    #[must_use]
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        let (numer_rational, numer_coefficient, denom) = self.closed_form();
+        let radicand = format_integer(&self.radicand, radix);
+        let surd = if numer_coefficient.abs().is_one() {
+            format!("√{radicand}")
+        } else {
+            format!(
+                "{}√{radicand}",
+                format_integer(&numer_coefficient.abs(), radix)
+            )
+        };
+        let numerator = match (numer_rational.is_zero(), numer_coefficient.is_negative()) {
+            (true, false) => surd,
+            (true, true) => format!("-{surd}"),
+            (false, false) => format!("{} + {surd}", format_integer(&numer_rational, radix)),
+            (false, true) => format!("{} - {surd}", format_integer(&numer_rational, radix)),
+        };
+        if denom.is_one() {
+            numerator
+        } else if numer_rational.is_zero() {
+            format!("{numerator}/{}", format_integer(&denom, radix))
+        } else {
+            format!("({numerator})/{}", format_integer(&denom, radix))
+        }
+    }
+
+    /// Returns `(P, N, Q)` such that the value equals `(P + √N) / Q` and `Q` divides `N - P²`
+    fn reduced_surd_form(&self) -> (BigInt, BigInt, BigInt) {
+        let (numer_rational, numer_coefficient, denom) = self.closed_form();
+        let n = &numer_coefficient * &numer_coefficient * &self.radicand;
+        let (p, q) = if numer_coefficient.is_positive() {
+            (numer_rational, denom)
+        } else {
+            (-numer_rational, -denom)
+        };
+        if (&n - &p * &p).is_multiple_of(&q) {
+            (p, n, q)
+        } else {
+            let q_abs = q.abs();
+            (p * &q_abs, n * &q_abs * &q_abs, q * q_abs)
+        }
+    }
+
+    /// The continued fraction expansion, which is eventually periodic.
+    ///
+    /// Returns the terms up to the end of the first period,
+    /// together with the index at which the period starts.
+    /// When the period does not appear within `max_terms` terms, the index is `None`.
+    #[must_use]
+    pub fn continued_fraction(&self, max_terms: usize) -> (Vec<BigInt>, Option<usize>) {
+        let (mut p, n, mut q) = self.reduced_surd_form();
+        let sqrt_n = n.sqrt();
+
+        let mut seen: HashMap<(BigInt, BigInt), usize> = HashMap::new();
+        let mut terms = vec![];
+        while terms.len() < max_terms {
+            if let Some(&start) = seen.get(&(p.clone(), q.clone())) {
+                return (terms, Some(start));
+            }
+            seen.insert((p.clone(), q.clone()), terms.len());
+
+            let term = floor_of_surd(&p, &sqrt_n, &q);
+            p = &term * &q - p;
+            q = (&n - &p * &p) / q;
+            terms.push(term);
+        }
+        (terms, None)
+    }
+}
+
+/// `floor((p + √n) / q)` for a non-square `n`, given `sqrt_n == floor(√n)`
+fn floor_of_surd(p: &BigInt, sqrt_n: &BigInt, q: &BigInt) -> BigInt {
+    if q.is_positive() {
+        (p + sqrt_n).div_floor(q)
+    } else {
+        // (p + √n) / q == (-p - √n) / (-q), and floor(-√n) == -floor(√n) - 1
+        (-p - sqrt_n - BigInt::one()).div_floor(&-q)
+    }
+}
+
+impl fmt::Display for QuadraticIrrational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str_radix(10))
+    }
+}
+
+/// The number of steps of Pollard's rho algorithm spent on each composite in [`square_free_decomposition`],
+/// which reliably finds the prime factors below about `MAX_FACTORIZATION_STEPS²`
+const MAX_FACTORIZATION_STEPS: usize = 1 << 16;
+
+/// Splits `n` into `(k, f)` such that `n == k² f` and `f` is square-free.
+///
+/// A composite part of `n` that cannot be split in [`MAX_FACTORIZATION_STEPS`] steps is taken into `k` when it is a square,
+/// and otherwise into `f` as square-free, which is certain when it has at most two prime factors
+fn square_free_decomposition(n: &BigUint) -> (BigUint, BigUint) {
+    if n.is_zero() {
+        return (BigUint::zero(), BigUint::one());
+    }
+    let mut root = BigUint::one();
+    let mut square_free = BigUint::one();
+    let (factors, unsplit) =
+        factorize_with_effort(&BigInt::from(n.clone()), MAX_FACTORIZATION_STEPS);
+    for (p, exponent) in factors {
+        // the prime factors of a positive integer are positive
+        let p = p.magnitude();
+        root *= p.pow(exponent / 2);
+        if exponent % 2 == 1 {
+            square_free *= p;
+        }
+    }
+    for composite in unsplit {
+        let composite = composite.magnitude();
+        let s = composite.sqrt();
+        if &s * &s == *composite {
+            root *= s;
+        } else {
+            square_free *= composite;
+        }
+    }
+    (root, square_free)
+}
+
+/// The exact square root of a non-negative rational
+pub fn sqrt(x: &BigRational) -> Result<Value, Error> {
+    if x.is_negative() {
+        return Err(Error::new(
+            ErrorKind::Arithmetic,
+            format!("Cannot take the square root of a negative number {x}"),
+        ));
+    }
+    // √(n/m) == √(nm) / m
+    let (_, nm) = (x.numer() * x.denom()).into_parts();
+    let (root, square_free) = square_free_decomposition(&nm);
+    let coefficient = BigRational::new(BigInt::from(root), x.denom().clone());
+    if square_free == BigUint::one() {
+        Ok(Value::Rational(coefficient))
+    } else {
+        Ok(QuadraticIrrational::new_value(
+            BigRational::zero(),
+            coefficient,
+            BigInt::from_biguint(Sign::Plus, square_free),
+        ))
+    }
+}
+
+/// The value of the purely periodic continued fraction `[p_1; p_2, ..., p_m, p_1, p_2, ...]`
+///
+/// All the terms must be positive, so that the continued fraction converges
+pub fn purely_periodic_continued_fraction(period: &[Value]) -> Result<Value, Error> {
+    let mut terms = vec![];
+    for term in period {
+        match term {
+            Value::Rational(r) if r.is_positive() => terms.push(r.clone()),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Arithmetic,
+                    format!("The terms inside `@rep` must be positive rationals, but found {term}"),
+                ))
+            }
+        }
+    }
+    if terms.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Syntax,
+            "`@rep` must contain at least one term",
+        ));
+    }
+
+    // y == [p_1; p_2, ..., p_m, y] == (P y + Q) / (R y + S),
+    // where [[P, Q], [R, S]] is the product of [[p_i, 1], [1, 0]]
+    let one = BigRational::one();
+    let zero = BigRational::zero();
+    let (mut pp, mut qq, mut rr, mut ss) = (one.clone(), zero.clone(), zero, one);
+    for t in &terms {
+        (pp, qq, rr, ss) = (&pp * t + &qq, pp, &rr * t + &ss, rr);
+    }
+
+    // R y^2 + (S - P) y - Q == 0, whose positive root is y
+    let b = &ss - &pp;
+    let discriminant = &b * &b + BigRational::from_integer(BigInt::from(4)) * &qq * &rr;
+    let two_r = BigRational::from_integer(BigInt::from(2)) * rr;
+    Value::Rational(-b)
+        .add(&sqrt(&discriminant)?)?
+        .div(&Value::Rational(two_r))
+}
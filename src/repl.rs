@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use periodicode::{open_bracket_depth, Interpreter};
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".periodicode_history"))
+}
+
+/// Reads lines from the terminal and executes them one by one, keeping `$_` and the radix context.
+/// An input line with an unclosed `(`, `[` or `{` is continued on the next line.
+pub fn run(ctx: &mut Interpreter, colored_errors: bool) -> rustyline::Result<()> {
+    let mut rl = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // The history file does not exist on the first run
+        let _ = rl.load_history(path);
+    }
+
+    ctx.set_echo_input(false);
+    ctx.set_source_name("<repl>");
+    let mut pending_lines: Vec<String> = vec![];
+
+    loop {
+        let raw_banner = ctx.banner(false);
+        let prompt = if pending_lines.is_empty() {
+            (
+                format!("{raw_banner} "),
+                format!("{} ", ctx.banner(ctx.colored())),
+            )
+        } else {
+            let continuation = format!("{:>width$} ", "...>", width = raw_banner.len());
+            (continuation.clone(), continuation)
+        };
+
+        match rl.readline(&prompt) {
+            Ok(line) => {
+                pending_lines.push(line);
+                if open_bracket_depth(&pending_lines.join("\n")) > 0 {
+                    continue;
+                }
+
+                let _ = rl.add_history_entry(pending_lines.join("\n"));
+                // the lexer continues a statement onto the next line while a bracket is open
+                let input = pending_lines.join("\n");
+                pending_lines.clear();
+
+                if let Err(e) = ctx.execute(&input) {
+                    eprintln!("{}", e.render(colored_errors));
+                }
+            }
+            // Ctrl-C discards the statement being typed
+            Err(ReadlineError::Interrupted) => pending_lines.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(path) = &history {
+        rl.save_history(path)?;
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::lexer::{tokenize, TokenKind};
 use crate::parse::numeric_literal::parse_numeric_literal_with_radix_context;
 use num_rational::BigRational;
 
@@ -7,11 +8,58 @@ fn numeric_literal(input: &str) -> BigRational {
     ans
 }
 
+/// The value of a whole numeric literal in `radix`, or the message of the error
+fn literal(input: &str, radix: u32) -> Result<String, String> {
+    parse_numeric_literal_with_radix_context(input, radix)
+        .map(|(ans, remaining)| {
+            assert!(remaining.is_empty());
+            ans.to_string()
+        })
+        .map_err(|e| e.message().to_owned())
+}
+
+fn rational(numer: i64, denom: i64) -> BigRational {
+    BigRational::new(numer.into(), denom.into())
+}
+
+/// The value of a program run in decimal
+fn eval(source: &str) -> String {
+    crate::evaluate(source, 10).unwrap().0.to_string()
+}
+
+fn kinds(input: &str) -> Vec<TokenKind> {
+    tokenize(input, 10)
+        .unwrap()
+        .into_iter()
+        .map(|token| token.kind)
+        .collect()
+}
+
+fn number(literal: &str) -> TokenKind {
+    TokenKind::Number(literal.to_owned())
+}
+
 #[test]
 fn parser_test() {
     let (ans, remaining) = parse_numeric_literal_with_radix_context("12.;", 10).unwrap();
     assert_eq!(ans.to_string(), "12");
     assert_eq!(remaining, ";");
+
+    // an `r` without repeating digits after it is not part of the literal
+    for (input, radix) in [
+        ("1.r", 10),
+        ("0x1.r", 10),
+        ("1.r", 16),
+        ("1;r", 60),
+        ("1;30,r", 60),
+    ] {
+        let (_, remaining) = parse_numeric_literal_with_radix_context(input, radix).unwrap();
+        assert_eq!(remaining, "r", "{input}");
+    }
+    for source in ["1.r", "@sexagesimal { 1;r }", "60#1;r"] {
+        let e = crate::evaluate(source, 10).unwrap_err();
+        assert_eq!(e.kind(), crate::error::ErrorKind::Syntax, "{source}");
+    }
 }
 
 #[test]
@@ -80,3 +128,1057 @@ fn test() {
         "1024"
     );
 }
+
+#[test]
+fn large_radix_test() {
+    use crate::evaluate;
+    use crate::print::{format_digit_expansion, format_fraction};
+
+    assert_eq!(literal("36#ZZ", 10).unwrap(), "1295");
+    assert_eq!(literal("7#0.r3", 10).unwrap(), "1/2");
+    assert_eq!(literal("16#ff", 2).unwrap(), "255");
+    // beyond radix 25, lowercase `p` and `r` are markers and uppercase `P` and `R` are digits
+    assert_eq!(literal("0.rP", 36).unwrap(), "5/7");
+    assert_eq!(literal("Rp1", 36).unwrap(), "54");
+    assert!(literal("z", 36).is_err());
+    // beyond radix 36, the digits are separated by `:`
+    assert_eq!(literal("1:30:00", 60).unwrap(), "5400");
+    assert_eq!(literal("0.15:r20:40", 60).unwrap(), "11045/43188");
+    assert_eq!(literal("60#0.r20", 10).unwrap(), "20/59");
+    assert_eq!(literal("1:30e-1", 60).unwrap(), "3/2");
+    assert_eq!(
+        literal("60#1:75", 10).unwrap_err(),
+        "invalid digit `75` in radix 60"
+    );
+    assert!(literal("1#1", 10).is_err());
+
+    assert_eq!(eval("@base 36 { ZZ }"), "1295");
+    assert_eq!(eval("@radix(7) { 0.r3 }"), "1/2");
+    assert_eq!(eval("@set_radix(@base 60); 1:30"), "90");
+    assert_eq!(eval("@set_radix(@radix(36)); 10"), "36");
+    assert_eq!(eval("@period(1/7, @base 60)"), "3");
+    assert!(evaluate("@base 1 { 1 }", 10).is_err());
+    assert!(evaluate("@radix(0x10) { 1 }", 10).is_err());
+
+    assert_eq!(format_fraction(&rational(-1295, 2), 36), "-ZZ/2");
+    assert_eq!(format_fraction(&rational(5401, 61), 60), "1:30:01/1:01");
+    assert_eq!(format_digit_expansion(&rational(1, 7), 36, 100), "0.r5");
+    assert_eq!(format_digit_expansion(&rational(5, 7), 36, 100), "0.rP");
+    assert_eq!(
+        format_digit_expansion(&rational(-61, 56), 60, 100),
+        "-1.05:21r25:42:51"
+    );
+}
+
+#[test]
+fn sexagesimal_test() {
+    use crate::error::ErrorKind;
+    use crate::evaluate;
+
+    // digits separated by `:` are sexagesimal without any prefix
+    assert_eq!(literal("12:34:56.r7", 10).unwrap(), "2672471/59");
+    assert_eq!(literal("12:34:56.r7", 16).unwrap(), "2672471/59");
+    // the Babylonian notation, in radix 60 or behind `60#`
+    assert_eq!(literal("1;24,51,10", 60).unwrap(), "30547/21600");
+    assert_eq!(literal("0;r08,34,17", 60).unwrap(), "1/7");
+    assert_eq!(literal("1:30;r20", 60).unwrap(), "5330/59");
+    assert_eq!(literal("60#1;30", 10).unwrap(), "3/2");
+    // the leading place may exceed 59, as the hours and degrees do
+    assert_eq!(literal("25:00:00", 10).unwrap(), "90000");
+    assert_eq!(literal("123;30", 60).unwrap(), "247/2");
+    assert_eq!(literal("75", 60).unwrap(), "75");
+    assert_eq!(
+        literal("1:60", 10).unwrap_err(),
+        "invalid digit `60` in radix 60"
+    );
+    // `;` without a digit after it is not a radix point, and neither is it elsewhere, as the lexer reads it
+    for (input, radix, expected) in [
+        ("12;", 60, ("12", ";")),
+        ("1;2", 10, ("1", ";2")),
+        ("1:30;r20", 12, ("90", ";r20")),
+    ] {
+        let (ans, remaining) = parse_numeric_literal_with_radix_context(input, radix).unwrap();
+        assert_eq!((ans.to_string().as_str(), remaining), expected, "{input}");
+    }
+
+    assert_eq!(eval("1:30 + 60#0;30"), "181/2");
+    assert_eq!(eval("@sexagesimal { 1;30 }"), "3/2");
+    assert_eq!(eval("@base 60 { @if (1) { 1;30 } }"), "3/2");
+    assert_eq!(eval("@radix(60) { 1;24,51,10 }"), "30547/21600");
+    // and so it is after `@set_radix` to radix 60, or when starting in radix 60
+    assert_eq!(eval("@set_radix(@sexagesimal); 0;30"), "1/2");
+    assert_eq!(eval("{ @set_radix(@base 60) }; 0;30"), "1/2");
+    assert_eq!(evaluate("0;30", 60).unwrap().0.to_string(), "1/2");
+    assert_eq!(evaluate("@decimal { 1;2 }", 60).unwrap().0.to_string(), "2");
+    assert_eq!(
+        eval("@set_radix(@sexagesimal); @set_radix(@decimal); 1;2"),
+        "2"
+    );
+    assert_eq!(eval("@hexadecimal { @set_radix(@sexagesimal) }; 1;2"), "2");
+    // elsewhere `;` separates statements
+    assert_eq!(eval("{1;2}"), "2");
+    assert_eq!(eval("@if (1) {2;3}"), "3");
+    assert_eq!(eval("@sexagesimal { @decimal { 1;2 } }"), "2");
+    assert_eq!(eval("@sexagesimal { 1; 2 }"), "2");
+    assert_eq!(eval("@babylonian { 1 } ; 2"), "2");
+    assert_eq!(eval("[1;2,3]"), "10/7");
+    assert_eq!(eval("@period(1/7, @sexagesimal)"), "3");
+
+    // `;` and `,` belong to a sexagesimal literal in the Babylonian notation, but not inside `[...]` or before a space
+    assert_eq!(kinds("60#1;24,51,10"), [number("60#1;24,51,10")]);
+    assert_eq!(kinds("60#1;r20"), [number("60#1;r20")]);
+    assert_eq!(
+        kinds("[1;2,3]"),
+        [
+            TokenKind::LBracket,
+            number("1"),
+            TokenKind::Semicolon,
+            number("2"),
+            TokenKind::Comma,
+            number("3"),
+            TokenKind::RBracket
+        ]
+    );
+    assert_eq!(
+        kinds("1; 2"),
+        [number("1"), TokenKind::Semicolon, number("2")]
+    );
+    assert_eq!(
+        kinds("@sexagesimal { @f(1;30, 2) }")[3..],
+        [
+            TokenKind::LParen,
+            number("1;30"),
+            TokenKind::Comma,
+            number("2"),
+            TokenKind::RParen,
+            TokenKind::RBrace
+        ]
+    );
+    assert_eq!(
+        kinds("16#1;2"),
+        [number("16#1"), TokenKind::Semicolon, number("2")]
+    );
+    // what can only be sexagesimal places is an error outside a sexagesimal context
+    for source in ["1;24,51,10", "1;r20", "@f(1;30, 2)", "@hex { 1;2,3 }"] {
+        let e = tokenize(source, 10).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Syntax, "{source}");
+    }
+}
+
+#[test]
+fn sexagesimal_output_test() {
+    use crate::print::{
+        format_babylonian_expansion, format_degrees_minutes_seconds, format_digit_expansion,
+        format_hours_minutes_seconds,
+    };
+
+    assert_eq!(
+        format_babylonian_expansion(&rational(1, 7), 100),
+        "0;r08,34,17"
+    );
+    assert_eq!(
+        format_babylonian_expansion(&rational(90000, 1), 100),
+        "25:00:00"
+    );
+    assert_eq!(
+        format_babylonian_expansion(&rational(247, 2), 100),
+        "2:03;30"
+    );
+    assert_eq!(
+        format_babylonian_expansion(&rational(2_672_471, 59), 100),
+        "12:34:56;r07"
+    );
+    // the Babylonian notation is only for radix 60
+    assert_eq!(
+        format_digit_expansion(&rational(1, 7), 60, 100),
+        "0.r08:34:17"
+    );
+    assert_eq!(format_digit_expansion(&rational(1, 3), 100, 100), "0.r33");
+    // the leading unit is unbounded in the mixed-radix formats
+    assert_eq!(
+        format_hours_minutes_seconds(&rational(360_000, 1), 100),
+        "100:00:00"
+    );
+    assert_eq!(
+        format_hours_minutes_seconds(&rational(-181, 2), 100),
+        "-0:01:30.5"
+    );
+    assert_eq!(
+        format_hours_minutes_seconds(&rational(1, 3), 100),
+        "0:00:00.r3"
+    );
+    assert_eq!(
+        format_degrees_minutes_seconds(&rational(247, 2), 100),
+        "123°30′00″"
+    );
+    assert_eq!(
+        format_degrees_minutes_seconds(&rational(1, 7), 100),
+        "0°08′34.r285714″"
+    );
+}
+
+#[test]
+fn signed_exponent_test() {
+    // `1e-5` is a literal where `e` is an exponent marker, and a subtraction where `e` is a digit
+    assert_eq!(eval("1e-5"), "1/100000");
+    assert_eq!(eval("2 * 1e-5 ^ 2"), "1/5000000000");
+    assert_eq!(eval("@hexadecimal { fe-1 }"), "253");
+    // it stays one literal in either reading, as if in parentheses
+    assert_eq!(eval("@hexadecimal { 2 * fe-1 }"), "506");
+    assert_eq!(eval("@hexadecimal { -fe-1 }"), "-253");
+    assert_eq!(eval("@hexadecimal { 1e+1 }"), "31");
+    assert_eq!(eval("@base 60 { 1e-1 }"), "1/60");
+    // the radix context at the time of evaluation decides, even inside a function
+    assert_eq!(
+        eval("@fn f() { 1e-1 }; @f() + @hexadecimal { @f() }"),
+        "291/10"
+    );
+    // a prefix decides by itself
+    assert_eq!(eval("@hexadecimal { 0d1e-2 }"), "1/100");
+    assert_eq!(eval("0x1e-2"), "28");
+    // spaces make it a subtraction in any radix
+    assert_eq!(eval("@hexadecimal { fe - 1 }"), "253");
+    // the literal is parsed once however deeply it is nested
+    let nested = format!("{}1{}", "{1e-5 + ".repeat(20), "}".repeat(20));
+    assert_eq!(eval(&nested), "5001/5000");
+}
+
+#[test]
+fn quadratic_irrational_test() {
+    use crate::quadratic::{purely_periodic_continued_fraction, sqrt};
+    use crate::value::Value;
+    use num_bigint::BigInt;
+
+    let int = |n: i64| Value::Rational(rational(n, 1));
+
+    let sqrt3 = sqrt(&rational(3, 1)).unwrap();
+    assert_eq!(sqrt3.to_string(), "√3");
+    assert_eq!(sqrt3.mul(&sqrt3).unwrap(), int(3));
+    assert_eq!(sqrt(&rational(12, 49)).unwrap().to_string(), "2√3/7");
+    assert_eq!(
+        sqrt(&rational(9, 4)).unwrap(),
+        Value::Rational(rational(3, 2))
+    );
+    assert!(sqrt(&rational(-1, 1)).is_err());
+    // large radicands are factorized by Pollard's rho algorithm, which gives up on what it cannot split quickly
+    let sqrt_of = |n: &str| sqrt(&numeric_literal(n)).unwrap().to_string();
+    assert_eq!(
+        sqrt_of("1000000037000000399000001323"),
+        "√1000000037000000399000001323"
+    );
+    assert_eq!(
+        sqrt_of("3000000096000001146000006048000011907"),
+        "1000000016000000063√3"
+    );
+    assert_eq!(
+        sqrt_of("4900000002184000000303280000013353600000183188"),
+        "2√1225000000546000000075820000003338400000045797"
+    );
+
+    // [1; @rep{1, 2}] == √3
+    let tail = purely_periodic_continued_fraction(&[int(1), int(2)]).unwrap();
+    assert_eq!(int(1).add(&tail.recip().unwrap()).unwrap(), sqrt3);
+
+    // the golden ratio
+    let phi = purely_periodic_continued_fraction(&[int(1)]).unwrap();
+    assert_eq!(phi.to_string(), "(1 + √5)/2");
+    let Value::Quadratic(q) = phi.sub(&int(10)).unwrap() else {
+        panic!("not a quadratic irrational")
+    };
+    assert_eq!(q.to_str_radix(16), "(-13 + √5)/2");
+    assert_eq!(phi.mul(&phi).unwrap(), phi.add(&int(1)).unwrap());
+
+    let Value::Quadratic(q) = int(2).sub(&sqrt3).unwrap() else {
+        panic!("not a quadratic irrational")
+    };
+    // 2 - √3 == [0; 3, @rep{1, 2}]
+    let (terms, period_start) = q.continued_fraction(100);
+    assert_eq!(terms, [0, 3, 1, 2].map(BigInt::from));
+    assert_eq!(period_start, Some(2));
+
+    assert!(sqrt3.add(&sqrt(&rational(2, 1)).unwrap()).is_err());
+    assert!(int(0).recip().is_err());
+}
+
+#[test]
+fn power_test() {
+    use crate::error::ErrorKind;
+    use crate::value::Value;
+    use num_bigint::BigInt;
+
+    let value = |numer: i64, denom: i64| Value::Rational(rational(numer, denom));
+
+    assert_eq!(value(2, 3).pow(&value(-2, 1)).unwrap(), value(9, 4));
+    assert_eq!(value(0, 1).pow(&value(0, 1)).unwrap(), value(1, 1));
+    assert_eq!(value(-8, 27).pow(&value(2, 3)).unwrap(), value(4, 9));
+    assert!(value(0, 1).pow(&value(-1, 1)).is_err());
+    assert!(value(-4, 1).pow(&value(1, 2)).is_err());
+    assert!(value(2, 1).pow(&value(1, 2)).is_err());
+
+    // a result with too many bits is refused instead of computed
+    let err = value(2, 1).pow(&value(4_000_000_000, 1)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Arithmetic);
+    assert!(value(1, 2).pow(&value(-1 << 40, 1)).is_err());
+    assert_eq!(
+        value(2, 1).pow(&value(1000, 1)).unwrap(),
+        Value::Rational(BigRational::from_integer(BigInt::from(2).pow(1000u32)))
+    );
+    // except for the powers of 0, 1 and -1, which stay small
+    let huge = Value::Rational(BigRational::from_integer(BigInt::from(10).pow(40u32)));
+    assert_eq!(value(-1, 1).pow(&huge).unwrap(), value(1, 1));
+    assert_eq!(
+        value(-1, 1).pow(&huge.add(&value(1, 1)).unwrap()).unwrap(),
+        value(-1, 1)
+    );
+    assert_eq!(value(0, 1).pow(&huge).unwrap(), value(0, 1));
+}
+
+#[test]
+fn comparison_test() {
+    use crate::quadratic::sqrt;
+    use crate::value::Value;
+    use crate::{Interpreter, Verbosity};
+    use std::cmp::Ordering;
+
+    let value = |input: &str| Value::Rational(numeric_literal(input));
+    let sqrt2 = sqrt(&numeric_literal("2")).unwrap();
+
+    assert_eq!(sqrt2.compare(&value("1.4142")).unwrap(), Ordering::Greater);
+    assert_eq!(sqrt2.compare(&value("1.4143")).unwrap(), Ordering::Less);
+    assert_eq!((-sqrt2.clone()).signum(), Ordering::Less);
+    // 2 - √3 > 0 even though the coefficient of √3 is negative
+    let two_minus_sqrt3 = value("2")
+        .sub(&sqrt(&numeric_literal("3")).unwrap())
+        .unwrap();
+    assert_eq!(two_minus_sqrt3.signum(), Ordering::Greater);
+    assert_eq!((-two_minus_sqrt3.clone()).abs(), two_minus_sqrt3);
+    assert_eq!(value("0").signum(), Ordering::Equal);
+    assert!(sqrt2
+        .compare(&sqrt(&numeric_literal("3")).unwrap())
+        .is_err());
+
+    let mut ctx = Interpreter::new(16);
+    ctx.set_verbosity(Verbosity::Silent);
+    let err = ctx.execute("@assert_gt(1/a, 0.8)").unwrap_err();
+    assert_eq!(
+        err.message(),
+        "ASSERTION FAILED: `left > right`\n\
+         left: 1/a # @decimal { 1/10 }\n\
+         right: 1/2 # @decimal { 1/2 }"
+    );
+    assert_eq!(
+        ctx.execute("@assert_approx(10, 11, 1)")
+            .unwrap()
+            .to_string(),
+        "16"
+    );
+}
+
+#[test]
+fn boolean_operator_test() {
+    use crate::evaluate;
+
+    assert_eq!(eval("1/3 < 0.34"), "1");
+    assert_eq!(eval("0.r9 == 1"), "1");
+    assert_eq!(eval("0.r9 != 1"), "0");
+    assert_eq!(eval("@sqrt(2) >= 1.5"), "0");
+    assert_eq!(eval("!0 + !5"), "1");
+    assert_eq!(eval("2 && -3"), "1");
+    // the right-hand side is not evaluated when the left-hand side decides
+    assert_eq!(eval("0 && 1/0"), "0");
+    assert_eq!(eval("7 || $undefined"), "1");
+    assert!(evaluate("1 && 1/0", 10).is_err());
+}
+
+#[test]
+fn conditional_test() {
+    use crate::evaluate;
+
+    let sign = "@if ($x < 0) { -1 } @else @if ($x == 0) { 0 } @else { 1 }";
+    for (x, expected) in [("-5", "-1"), ("0", "0"), ("1/3", "1")] {
+        assert_eq!(eval(&format!("$x = {x}; {sign}")), expected);
+    }
+    // the other branch is not evaluated
+    assert_eq!(eval("@if (1) { 2 } @else { 1/0 }"), "2");
+    assert_eq!(eval("@if (0) { 1/0 } @else { 3 }"), "3");
+    // without `@else`, a false condition leaves `$_` as it is
+    assert_eq!(eval("7; @if (0) { 1 }"), "7");
+    // a radix change inside a branch persists, as with any undecorated block
+    assert_eq!(
+        evaluate("@if (1) { @set_radix(@hexadecimal) }", 10)
+            .unwrap()
+            .1,
+        16
+    );
+    assert_eq!(eval("@if (1) {\n  2\n} @else {\n  3\n}"), "2");
+    assert!(evaluate("@if (1) { 2 }\n@else { 3 }", 10).is_err());
+    assert!(evaluate("@if 1 { 2 }", 10).is_err());
+}
+
+#[test]
+fn loop_test() {
+    use crate::{evaluate, ErrorKind, Interpreter, Verbosity};
+
+    // each iteration sets `$_`
+    assert_eq!(eval("1; @repeat(10) { $_ * 2 }"), "1024");
+    assert_eq!(eval("7; @repeat(0) { 1 }"), "7");
+    assert_eq!(eval("$x = 1; @while ($x < 100) { $x = $x * 3 }"), "243");
+    assert_eq!(eval("7; @while (0) { 1 }"), "7");
+    // the count is read in the radix context
+    assert_eq!(eval("@hex { 0; @repeat(10) { $_ + 1 } }"), "16");
+    assert_eq!(
+        evaluate("@repeat(-1) { 1 }", 10).unwrap_err().kind(),
+        ErrorKind::Arithmetic
+    );
+
+    let mut ctx = Interpreter::new(10);
+    ctx.set_verbosity(Verbosity::Silent);
+    ctx.set_iteration_limit(3);
+    assert!(ctx.execute("@repeat(3) { 1 }").is_ok());
+    assert_eq!(
+        ctx.execute("@repeat(4) { 1 }").unwrap_err().kind(),
+        ErrorKind::IterationLimit
+    );
+    assert_eq!(
+        ctx.execute("$n = 0; @while ($n < 4) { $n = $n + 1 }")
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IterationLimit
+    );
+    assert_eq!(ctx.execute("$n").unwrap().to_string(), "3");
+}
+
+#[test]
+fn function_test() {
+    use crate::{evaluate, ErrorKind, Interpreter, Verbosity};
+
+    let kind = |source: &str| evaluate(source, 10).unwrap_err().kind();
+
+    assert_eq!(
+        eval("@fn square($x) { $x * $x }; @square(3) + @square(4)"),
+        "25"
+    );
+    assert_eq!(eval("@fn zero() { 0 }; 7; @zero() + $_"), "7");
+    // a definition keeps `$_`
+    assert_eq!(eval("7; @fn f() { 0 }"), "7");
+    assert_eq!(
+        eval("@fn fact($n) { @if ($n <= 1) { 1 } @else { $n * @fact($n - 1) } }; @fact(20)"),
+        "2432902008176640000"
+    );
+    // the variables of the file are read when the function is called
+    assert_eq!(
+        eval("@fn f($x) { $x + $k }; $k = 1; $a = @f(1); $k = 2; $a * @f(1)"),
+        "6"
+    );
+    // an assignment inside the body is local to the call
+    assert_eq!(
+        eval("$y = 1; @fn f($x) { $y = $x; $y * 2 }; @f(5) + $y"),
+        "11"
+    );
+    assert_eq!(
+        kind("@fn f($x) { $y = $x }; @f(1); $y"),
+        ErrorKind::UndefinedVariable
+    );
+    assert_eq!(
+        kind("@fn f($x) { $x }; @f(1); $x"),
+        ErrorKind::UndefinedVariable
+    );
+    // a later definition replaces an earlier one
+    assert_eq!(eval("@fn f() { 1 }; @fn f() { 2 }; @f()"), "2");
+
+    assert_eq!(kind("@g(1)"), ErrorKind::UnknownIdentifier);
+    assert_eq!(kind("@fn f($x) { $x }; @f(1, 2)"), ErrorKind::Syntax);
+    assert_eq!(kind("@fn load() { 1 }"), ErrorKind::Syntax);
+    assert_eq!(kind("@fn hex() { 1 }"), ErrorKind::Syntax);
+    assert_eq!(kind("@fn assert_eq() { 1 }"), ErrorKind::Syntax);
+    assert_eq!(kind("@fn f($x, $x) { 1 }"), ErrorKind::Syntax);
+    assert_eq!(kind("@fn f() { @fn g() { 1 } }"), ErrorKind::Syntax);
+
+    // a function outlives the input that defined it, and so does the text of its body
+    let mut ctx = Interpreter::new(10);
+    ctx.set_verbosity(Verbosity::Silent);
+    ctx.execute("@fn inverse($x) { 1 / $x }").unwrap();
+    let err = ctx.execute("1\n@inverse(0)").unwrap_err();
+    assert_eq!(
+        err.render(false),
+        "error[division_by_zero]: Division by zero
+ --> <command line>:1:19
+  |
+1 | @fn inverse($x) { 1 / $x }
+  |                   ^^^^^^
+  = note: in `@inverse`, called from <command line>:2:1"
+    );
+
+    // each call of `@count` nests the evaluation by two levels, the call and the `@if` in its body,
+    // and the innermost also evaluates the `0` in its block
+    let count = "@fn count($n) { @if ($n == 0) { 0 } @else { @count($n - 1) } }";
+    assert!(evaluate(&format!("{count}; @count(40)"), 10).is_ok());
+    assert_eq!(
+        evaluate(&format!("{count}; @count(500)"), 10)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::RecursionLimit
+    );
+    let mut ctx = Interpreter::new(10);
+    ctx.set_verbosity(Verbosity::Silent);
+    ctx.set_depth_limit(8);
+    ctx.execute(count).unwrap();
+    assert!(ctx.execute("@count(2)").is_ok());
+    assert!(ctx.execute("@count(3)").is_err());
+}
+
+#[test]
+fn depth_limit_test() {
+    use crate::{evaluate, ErrorKind};
+
+    // the default depth limit fits in the 2 MiB stack of a spawned thread, even in an unoptimized build,
+    // however the levels are nested
+    let thread = std::thread::Builder::new().stack_size(2 * 1024 * 1024);
+    let handle = thread.spawn(|| {
+        let kind = |source: &str| evaluate(source, 10).unwrap_err().kind();
+        let deep_call = "@fn f($n) { { { @if ($n == 0) { 0 } @else { @f($n - 1) } } } }; @f(1000)";
+        assert_eq!(kind(deep_call), ErrorKind::RecursionLimit);
+        let blocks = |depth: usize, inner: &str| {
+            format!("{}{inner}{}", "{ ".repeat(depth), " }".repeat(depth))
+        };
+        let deep_body = format!("@fn f($n) {}; @f(1000)", blocks(30, "@f($n - 1)"));
+        assert_eq!(kind(&deep_body), ErrorKind::RecursionLimit);
+        let deep_sum = format!("1{}", " + 1".repeat(1000));
+        assert_eq!(kind(&deep_sum), ErrorKind::RecursionLimit);
+        // and so does the limit on the nesting of the code, which is checked when parsing
+        assert!(evaluate(&blocks(31, "1"), 10).is_ok());
+        assert_eq!(kind(&blocks(32, "1")), ErrorKind::RecursionLimit);
+        for source in [
+            blocks(1000, "1"),
+            format!("{}1{}", "@sqrt(".repeat(1000), ")".repeat(1000)),
+            format!("{}1", "-".repeat(1000)),
+            format!("1{}", "^1".repeat(1000)),
+            format!("$a{}", " = $a".repeat(1000)),
+            format!("@if (0) {{ 0 }}{}", " @else @if (0) { 0 }".repeat(1000)),
+        ] {
+            assert_eq!(kind(&source), ErrorKind::RecursionLimit);
+        }
+    });
+    handle.unwrap().join().unwrap();
+}
+
+#[test]
+fn builtin_registry_test() {
+    use crate::{Argument, ArgumentKind, Builtin, ErrorKind, Interpreter, Value, Verbosity};
+
+    let mut ctx = Interpreter::new(10);
+    ctx.set_verbosity(Verbosity::Silent);
+
+    // `@scale(x, "label")`, with the label optional
+    let scale = Builtin::new(
+        "scale",
+        &[ArgumentKind::Expression, ArgumentKind::String],
+        |_, args| match &args[..] {
+            [Argument::Value(x), Argument::String(label)] if label == "double" => x.add(x),
+            [Argument::Value(x), ..] => Ok(x.clone()),
+            _ => unreachable!(),
+        },
+    )
+    .with_optional(1);
+    ctx.register_builtin(scale).unwrap();
+    assert_eq!(ctx.execute("@scale(3)").unwrap().to_string(), "3");
+    assert_eq!(
+        ctx.execute("@scale(3, \"double\")").unwrap().to_string(),
+        "6"
+    );
+    assert_eq!(
+        ctx.execute("@scale(3, 4)").unwrap_err().kind(),
+        ErrorKind::Syntax
+    );
+    assert_eq!(
+        ctx.execute("@scale()").unwrap_err().kind(),
+        ErrorKind::Syntax
+    );
+    assert_eq!(
+        ctx.execute("@scale(1, \"a\", 2)")
+            .unwrap_err()
+            .render(false)
+            .lines()
+            .next(),
+        Some("error[syntax]: The built-in function `@scale` expects one or two arguments")
+    );
+
+    // `@twice { ... }` evaluates the block twice
+    let twice = Builtin::new("twice", &[ArgumentKind::Block], |ctx, args| {
+        let [Argument::Block(block)] = &args[..] else {
+            unreachable!()
+        };
+        ctx.eval_block_argument(block)?;
+        ctx.eval_block_argument(block)
+    });
+    ctx.register_builtin(twice).unwrap();
+    assert_eq!(
+        ctx.execute("$n = 1; @twice { $n = $n * 3 }")
+            .unwrap()
+            .to_string(),
+        "9"
+    );
+    assert_eq!(
+        ctx.execute("$n = 1; @twice(\"$n = $n + 1\")")
+            .unwrap()
+            .to_string(),
+        "3"
+    );
+
+    // a registered built-in cannot be redefined by `@fn`, and the keywords cannot be registered
+    assert_eq!(
+        ctx.execute("@fn twice() { 1 }").unwrap_err().kind(),
+        ErrorKind::Syntax
+    );
+    for keyword in ["if", "rep", "hex"] {
+        let builtin = Builtin::new(keyword, &[], |ctx, _| Ok(ctx.previous_value().clone()));
+        assert!(ctx.register_builtin(builtin).is_err());
+    }
+    // the built-ins of the language can be replaced
+    let sqrt = Builtin::new(
+        "sqrt",
+        &[ArgumentKind::Expression],
+        |_, _| Ok(Value::zero()),
+    );
+    ctx.register_builtin(sqrt).unwrap();
+    assert_eq!(ctx.execute("@sqrt(4)").unwrap().to_string(), "0");
+}
+
+#[test]
+fn summary_test() {
+    use crate::print::{format_summary, write_summary};
+    use crate::quadratic::sqrt;
+    use crate::value::Value;
+
+    let seven_sixths = Value::Rational(numeric_literal("1.1r6"));
+    assert_eq!(
+        format_summary(&seven_sixths, 10, 100, false),
+        "frac: 7/6\ncont: [1; 6]\ndigt: 1.1r6\n"
+    );
+    assert_eq!(
+        format_summary(&seven_sixths, 12, 100, false),
+        "frac: 7/6 # @decimal { 7/6 }\n\
+         cont: [1; 6] # @decimal { [1; 6] }\n\
+         digt: 1.2 # @decimal { 1.1r6 }\n"
+    );
+    assert!(format_summary(&seven_sixths, 12, 100, true)
+        .contains(" \x1b[2;32m# @decimal { 1.1r6 }\x1b[00m\n"));
+    assert!(!format_summary(&seven_sixths, 10, 100, true).contains('\x1b'));
+    // radix 60 adds the Babylonian notation and the mixed-radix formats, without a decimal comment
+    let hundred_hours = Value::Rational(numeric_literal("360000"));
+    assert!(format_summary(&hundred_hours, 60, 100, false).ends_with(
+        "digt: 1:40:00:00 # @decimal { 360000 }\n\
+         baby: 1:40:00:00\n\
+         time: 100:00:00\n\
+         angl: 360000°00′00″\n"
+    ));
+
+    let sqrt3 = sqrt(&numeric_literal("3")).unwrap();
+    let mut out = String::from("$_ = √3\n");
+    write_summary(&mut out, &sqrt3, 10, 100, false).unwrap();
+    assert_eq!(out, "$_ = √3\nfrac: √3\ncont: [1; @rep{1, 2}]\n");
+}
+
+#[test]
+fn digit_expansion_test() {
+    use crate::number_theory::expansion_lengths;
+    use crate::print::format_digit_expansion;
+    use big_s::S;
+    use num_bigint::BigInt;
+
+    let digits = |numer: &str, denom: &str, radix: u32, max_period_digits: usize| {
+        let ans = BigRational::new(numer.parse().unwrap(), denom.parse().unwrap());
+        format_digit_expansion(&ans, radix, max_period_digits)
+    };
+    assert_eq!(digits("1", "7", 10, 100), "0.r142857");
+    assert_eq!(digits("7", "6", 10, 100), "1.1r6");
+    assert_eq!(digits("-1", "12", 10, 100), "-0.08r3");
+    assert_eq!(digits("1", "12", 12, 100), "0.1");
+    assert_eq!(digits("1", "10", 2, 100), "0.0r0011");
+    assert_eq!(digits("25", "8", 10, 100), "3.125");
+    assert_eq!(digits("1", "7", 10, 4), "0.r14…57 (period 6)");
+    assert_eq!(digits("1", "7", 10, 5), "0.r142…57 (period 6)");
+
+    // only the first and last digits of a long period are computed
+    assert_eq!(digits("1", "1000003", 10, 6), "0.r000…333 (period 166667)");
+    assert_eq!(
+        digits("1", "1000000000000000000000000000057", 10, 6),
+        "0.r000…807 (period 333333333333333333333333333352)"
+    );
+
+    let lengths = |q: &str, radix: u32| {
+        let (preperiod, period) = expansion_lengths(&q.parse::<BigInt>().unwrap(), radix);
+        (preperiod, period.to_string())
+    };
+    assert_eq!(lengths("1", 10), (0, S("0")));
+    assert_eq!(lengths("250", 10), (3, S("0")));
+    assert_eq!(lengths("44", 10), (2, S("2")));
+    assert_eq!(lengths("4000007", 10), (0, S("4602")));
+    assert_eq!(lengths("7", 2), (0, S("3")));
+    assert_eq!(lengths("10206", 6), (6, S("2")));
+    assert_eq!(lengths("1024", 8), (4, S("0")));
+    assert_eq!(
+        lengths("170141183460469231731687303715884105727", 2),
+        (0, S("127"))
+    );
+}
+
+#[test]
+fn period_builtin_test() {
+    use crate::{evaluate, ErrorKind};
+
+    assert_eq!(eval("@period(1/97)"), "96");
+    assert_eq!(eval("@period(1/97, @dozenal)"), "16");
+    assert_eq!(eval("@preperiod(-7/6)"), "1");
+    assert_eq!(eval("@preperiod(7/6, @dozenal)"), "1");
+    assert_eq!(eval("@period(7/6, @dozenal)"), "0");
+    assert_eq!(eval("@period(3)"), "0");
+    // the radix context applies when no radix is given, in which `101` is five
+    assert_eq!(eval("@set_radix(@binary); @period(1/101)"), "4");
+    assert_eq!(eval("@period(1/1000003)"), "166667");
+    assert_eq!(
+        evaluate("@period(@sqrt(2))", 10).unwrap_err().kind(),
+        ErrorKind::Arithmetic
+    );
+    assert_eq!(
+        evaluate("@period(1, 12)", 10).unwrap_err().kind(),
+        ErrorKind::Syntax
+    );
+}
+
+#[test]
+fn json_summary_test() {
+    use crate::error::Span;
+    use crate::print::format_json_summary;
+    use crate::quadratic::sqrt;
+    use crate::value::Value;
+
+    let source = "1;\n  -7/6";
+    assert_eq!(Span::new(5, 9).line_and_column(source), (2, 3));
+    assert_eq!(
+        format_json_summary(&Value::Rational(-numeric_literal("1.1r6")), 10, 100, "a\"b", 2, 3),
+        "{\"file\":\"a\\\"b\",\"line\":2,\"column\":3,\"radix\":10,\"type\":\"rational\",\
+         \"numerator\":\"-7\",\"denominator\":\"6\",\"continued_fraction\":[\"-2\",\"1\",\"5\"],\
+         \"negative\":true,\"integral_digits\":\"1\",\"non_repeating_digits\":\"1\",\"repeating_digits\":\"6\",\
+         \"repeating_digits_tail\":\"\",\"elided\":false,\"period_length\":1}"
+    );
+    assert!(format_json_summary(&Value::Rational(numeric_literal("255")), 16, 100, "", 1, 1)
+        .contains("\"numerator\":\"ff\",\"denominator\":\"1\",\"continued_fraction\":[\"ff\"],\
+                   \"negative\":false,\"integral_digits\":\"ff\",\"non_repeating_digits\":\"\",\"repeating_digits\":\"\",\"repeating_digits_tail\":\"\",\"elided\":false,\"period_length\":0}"));
+    // an elided period keeps its first and last digits apart
+    let ans = Value::Rational(BigRational::new(1.into(), 1_000_003.into()));
+    assert!(format_json_summary(&ans, 10, 6, "", 1, 1).ends_with(
+        "\"repeating_digits\":\"000\",\"repeating_digits_tail\":\"333\",\"elided\":true,\"period_length\":166667}"
+    ));
+
+    let sqrt3 = sqrt(&numeric_literal("3")).unwrap();
+    assert!(format_json_summary(&sqrt3, 10, 100, "", 1, 1).ends_with(
+        "\"type\":\"quadratic_irrational\",\"closed_form\":\"√3\",\
+         \"continued_fraction\":[\"1\"],\"continued_fraction_period\":[\"1\",\"2\"]}"
+    ));
+}
+
+#[test]
+fn test_mode_test() {
+    use crate::{ErrorKind, Interpreter, Verbosity};
+
+    let mut ctx = Interpreter::new(10);
+    ctx.set_verbosity(Verbosity::Silent);
+    ctx.set_test_mode(true);
+    let value = ctx
+        .execute(
+            "@assert_eq(1, 1)\n\
+             @assert_eq(1, 2)\n\
+             @should_fail { @assert_eq(2, 3) }\n\
+             @should_fail { 4 }\n\
+             5",
+        )
+        .unwrap();
+    assert_eq!(value.to_string(), "5");
+    let report = ctx.test_report();
+    assert_eq!(report.passed, 2);
+    let kinds: Vec<ErrorKind> = report.failures.iter().map(crate::Error::kind).collect();
+    assert_eq!(kinds, [ErrorKind::Assertion, ErrorKind::DidNotFail]);
+    assert!(report.failures[1].render(false).contains(":4:1"));
+
+    // a failure other than the expected one is an assertion failure as well
+    ctx.execute("@should_fail(\"1/0\", \"Division\")\n@should_fail(\"1/0\", @arithmetic)")
+        .unwrap();
+    assert_eq!(ctx.test_report().passed, 3);
+    assert_eq!(
+        ctx.test_report().failures[2].kind(),
+        ErrorKind::UnexpectedFailure
+    );
+
+    // any other error stops the execution
+    assert!(ctx.execute("1/0\n@assert_eq(1, 1)").is_err());
+    assert_eq!(ctx.test_report().passed, 3);
+}
+
+#[test]
+fn error_diagnostic_test() {
+    use crate::error::{Error, ErrorKind, Span};
+    use crate::{Interpreter, Verbosity};
+
+    let mut ctx = Interpreter::new(10);
+    ctx.set_verbosity(Verbosity::Silent);
+    let err = ctx
+        .execute("# comment\n\n\n\n\n\n1; 2 * (3 + 4")
+        .unwrap_err();
+    assert_eq!(
+        err.render(false),
+        "error[syntax]: Mismatched parenthesis
+ --> <command line>:7:14
+  |
+7 | 1; 2 * (3 + 4
+  |              ^"
+    );
+
+    let err = ctx.execute("$x / 2").unwrap_err();
+    assert!(err.render(false).ends_with("1 | $x / 2\n  | ^^"));
+
+    // an error from a loaded file keeps its own line and notes where it was loaded from
+    let err = Error::new(ErrorKind::DivisionByZero, "Division by zero")
+        .with_span(Span::new(6, 9))
+        .locate("inner.periodicode", "1\n2\n1 + 1/0\n")
+        .with_span(Span::new(7, 26))
+        .locate(
+            "outer.periodicode",
+            "$x = 1\n@load { \"inner.periodicode\" }",
+        );
+    assert_eq!(
+        err.render(false),
+        "error[division_by_zero]: Division by zero
+ --> inner.periodicode:3:3
+  |
+3 | 1 + 1/0
+  |   ^^^
+  = note: loaded from outer.periodicode:2:1"
+    );
+}
+
+#[test]
+fn lexer_test() {
+    assert_eq!(
+        kinds("!a<=b==c&&d||e>f"),
+        [
+            TokenKind::Bang,
+            number("a"),
+            TokenKind::LessEqual,
+            number("b"),
+            TokenKind::EqualEqual,
+            number("c"),
+            TokenKind::AndAnd,
+            number("d"),
+            TokenKind::OrOr,
+            number("e"),
+            TokenKind::Greater,
+            number("f"),
+        ]
+    );
+
+    // the name after `@fn` is not a numeric literal
+    assert_eq!(
+        kinds("@fn beef($x)"),
+        [
+            TokenKind::At("fn".to_owned()),
+            TokenKind::Identifier("beef".to_owned()),
+            TokenKind::LParen,
+            TokenKind::Dollar("x".to_owned()),
+            TokenKind::RParen,
+        ]
+    );
+
+    // the sign after `p`, or after an `e` that the prefix makes an exponent marker, belongs to the literal
+    assert_eq!(kinds("0x1.p-10"), [number("0x1.p-10")]);
+    assert_eq!(kinds("0d1e-5"), [number("0d1e-5")]);
+    // but not when the prefix makes `e` a digit
+    assert_eq!(
+        kinds("0x1e-2"),
+        [number("0x1e"), TokenKind::Minus, number("2")]
+    );
+    // nor without a prefix, when the parser makes a literal that holds both readings
+    assert_eq!(kinds("1e-5"), [number("1e"), TokenKind::Minus, number("5")]);
+    assert_eq!(
+        kinds("15#1e-2"),
+        [number("15#1e"), TokenKind::Minus, number("2")]
+    );
+    // `#` after a decimal integer gives the radix of the literal, but starts a comment elsewhere
+    assert_eq!(kinds("60#1:30.r20"), [number("60#1:30.r20")]);
+    assert_eq!(kinds("1 #1"), [number("1")]);
+    assert_eq!(kinds("1# one"), [number("1")]);
+    // and so it does when what follows is not a literal in that radix
+    assert_eq!(kinds("1#one"), [number("1")]);
+    assert_eq!(
+        kinds("3#comment\n2"),
+        [number("3"), TokenKind::Newline, number("2")]
+    );
+    assert_eq!(kinds("7#1e-2"), [number("7#1e-2")]);
+    assert_eq!(kinds("36#ZZ"), [number("36#ZZ")]);
+    assert_eq!(
+        kinds("$e-1"),
+        [
+            TokenKind::Dollar("e".to_owned()),
+            TokenKind::Minus,
+            number("1")
+        ]
+    );
+
+    assert_eq!(
+        kinds("@ assert_eq(.r3, \"a\\\"b\") # comment\n;"),
+        [
+            TokenKind::At("assert_eq".to_owned()),
+            TokenKind::LParen,
+            number(".r3"),
+            TokenKind::Comma,
+            TokenKind::Str("a\"b".to_owned()),
+            TokenKind::RParen,
+            TokenKind::Newline,
+            TokenKind::Semicolon,
+        ]
+    );
+    // newlines inside brackets do not terminate the statement
+    assert_eq!(
+        kinds("{\n1 # one\n}\n(\n)"),
+        [
+            TokenKind::LBrace,
+            number("1"),
+            TokenKind::RBrace,
+            TokenKind::Newline,
+            TokenKind::LParen,
+            TokenKind::RParen,
+        ]
+    );
+
+    assert!(tokenize("\"unterminated", 10).is_err());
+    assert!(tokenize("1 & 2", 10).is_err());
+}
+
+#[test]
+fn open_bracket_depth_test() {
+    use crate::open_bracket_depth;
+
+    assert_eq!(open_bracket_depth("@assert_eq(1, 1)"), 0);
+    assert_eq!(open_bracket_depth("@hexadecimal { 1;"), 1);
+    assert_eq!(open_bracket_depth("@load { \"{(.periodicode\" # {"), 1);
+    assert_eq!(open_bracket_depth("[1; (2"), 2);
+    assert_eq!(open_bracket_depth("@base 36 { 36#Z"), 1);
+    // the strings and comments are those of the lexer, even across lines
+    assert_eq!(open_bracket_depth("@load {\n\"a\\\"(\" # (\n"), 1);
+    assert_eq!(open_bracket_depth("(1 +\n 2)"), 0);
+    // an input that cannot be tokenized is left for the interpreter to report
+    assert_eq!(open_bracket_depth("(1 & 2"), 0);
+}
+
+#[test]
+fn parse_program_test() {
+    use crate::ast::{BinaryOp, BuiltinArgument, ExprKind, UnaryOp};
+    use crate::builtin::{Argument, Block, BlockBody, Builtins, ExpectedFailure};
+    use crate::error::ErrorKind;
+    use crate::parse;
+
+    let builtins = Builtins::default();
+    let parse_program = |source: &str| parse::parse_program(source, &builtins, 10);
+
+    let program = parse_program("-2^2; $a = [1; @rep{2}]\n\n@hex { beef }").unwrap();
+    assert_eq!(program.statements.len(), 3);
+    assert!(!program.statements[0].print);
+    assert!(program.statements[1].print);
+
+    // `^` binds tighter than unary minus
+    let ExprKind::Unary(UnaryOp::Minus, operand) = &program.statements[0].expr.kind else {
+        panic!("not a negation")
+    };
+    assert!(matches!(
+        operand.kind,
+        ExprKind::Binary(BinaryOp::Pow, _, _)
+    ));
+
+    let ExprKind::Assign(name, value) = &program.statements[1].expr.kind else {
+        panic!("not an assignment")
+    };
+    assert_eq!(name, "a");
+    assert!(matches!(
+        &value.kind,
+        ExprKind::ContinuedFraction { terms, period: Some(period) } if terms.len() == 1 && period.len() == 1
+    ));
+
+    // a literal is read only at evaluation, so that `beef` is not rejected here
+    let ExprKind::RadixBlock(16, exprs) = &program.statements[2].expr.kind else {
+        panic!("not a radix block")
+    };
+    assert!(matches!(&exprs[0].kind, ExprKind::Literal(literal) if literal == "beef"));
+
+    let program = parse_program("@load {\n  \"a\";\n  \"b\"\n}\n[1;\n 2]").unwrap();
+    assert_eq!(program.statements.len(), 2);
+    // `@load` is a built-in taking a block of strings
+    let ExprKind::Builtin { builtin, args } = &program.statements[0].expr.kind else {
+        panic!("not a built-in")
+    };
+    assert_eq!(builtin.name(), "load");
+    assert!(matches!(
+        &args[..],
+        [BuiltinArgument::Other(Argument::Strings(files))] if files.len() == 2
+    ));
+    assert!(parse_program("@load_dirty({ \"a\" })").is_ok());
+    assert!(parse_program("@load { 1 }").is_err());
+
+    // `||` < `&&` < comparisons < `+`
+    let program = parse_program("1 || 2 && 3 < 4 + 5").unwrap();
+    let ExprKind::Binary(BinaryOp::Or, _, rhs) = &program.statements[0].expr.kind else {
+        panic!("not a disjunction")
+    };
+    let ExprKind::Binary(BinaryOp::And, _, rhs) = &rhs.kind else {
+        panic!("not a conjunction")
+    };
+    assert!(matches!(
+        &rhs.kind,
+        ExprKind::Binary(BinaryOp::Lt, _, rhs) if matches!(rhs.kind, ExprKind::Binary(BinaryOp::Add, _, _))
+    ));
+    assert!(parse_program("1 < 2 < 3").is_err());
+    assert!(parse_program("(1 < 2) < 3").is_ok());
+
+    let program = parse_program(
+        "@should_fail({ 1 / 0 }, @division_by_zero); @should_fail(\"*\", \"operator\")",
+    )
+    .unwrap();
+    let ExprKind::Builtin { builtin, args } = &program.statements[0].expr.kind else {
+        panic!("not a built-in")
+    };
+    assert_eq!(builtin.name(), "should_fail");
+    assert!(matches!(
+        &args[..],
+        [
+            BuiltinArgument::Other(Argument::Block(Block(BlockBody::Parsed(_)))),
+            BuiltinArgument::Other(Argument::ExpectedFailure(ExpectedFailure::Kind(
+                ErrorKind::DivisionByZero
+            ))),
+        ]
+    ));
+    let ExprKind::Builtin { args, .. } = &program.statements[1].expr.kind else {
+        panic!("not a built-in")
+    };
+    assert!(matches!(
+        &args[..],
+        [
+            BuiltinArgument::Other(Argument::Block(Block(BlockBody::Source(source)))),
+            BuiltinArgument::Other(Argument::ExpectedFailure(ExpectedFailure::Message(part))),
+        ] if &**source == "*" && part == "operator"
+    ));
+    // the argument kinds and counts are checked uniformly
+    assert!(parse_program("@sqrt()").is_err());
+    assert!(parse_program("@sqrt(1, 2)").is_err());
+    assert!(parse_program("@set_radix(2)").is_err());
+    assert!(parse_program("@should_fail()").is_err());
+    assert!(parse_program("@should_fail(\"1\", @unknown_kind)").is_err());
+    assert!(parse_program("@should_fail(\"1\", 2)").is_err());
+
+    // a call is looked up only when evaluated, since the function may be defined in another file
+    assert!(matches!(
+        &parse_program("@unknown(1)").unwrap().statements[0].expr.kind,
+        ExprKind::Call { name, args } if name == "unknown" && args.len() == 1
+    ));
+    assert!(parse_program("@unknown").is_err());
+    assert!(parse_program("@hex {\n 1;\n").is_err());
+    assert!(parse_program("(1 + 2").is_err());
+}
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+
+use periodicode::{Error, ErrorKind, Interpreter, TestReport, Verbosity};
+
+use crate::cli::{Options, Source};
+
+/// The outcome of running a single file as a test suite
+struct FileResult {
+    path: String,
+    report: TestReport,
+
+    /// The error that stopped the file from being executed to the end
+    error: Option<Error>,
+}
+
+impl FileResult {
+    fn status(&self) -> &'static str {
+        if self.error.is_some() {
+            "ERROR"
+        } else if self.report.failures.is_empty() {
+            "ok"
+        } else {
+            "FAILED"
+        }
+    }
+
+    fn succeeded(&self) -> bool {
+        self.error.is_none() && self.report.failures.is_empty()
+    }
+}
+
+/// The `.periodicode` files in `dir` and its subdirectories, in alphabetical order
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        Error::new(
+            ErrorKind::Io,
+            format!("cannot read the directory {}: {e}", dir.display()),
+        )
+    })?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            collect_scripts(&path, scripts)?;
+        } else if path.extension().is_some_and(|ext| ext == "periodicode") {
+            scripts.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn run_file(path: &str, options: &Options) -> FileResult {
+    let mut ctx = Interpreter::new(options.radix_context);
+    ctx.set_verbosity(Verbosity::Silent);
+    ctx.set_test_mode(true);
+    ctx.set_iteration_limit(options.iteration_limit);
+    ctx.set_depth_limit(crate::DEPTH_LIMIT);
+    ctx.set_max_period_digits(options.max_period_digits);
+    let error = ctx.load_file(path, options.radix_context).err();
+    FileResult {
+        path: path.to_owned(),
+        report: ctx.test_report().clone(),
+        error,
+    }
+}
+
+/// Runs each file, and each `.periodicode` file inside each directory, given on the command line as a test suite.
+/// The failures are written to the standard error as they are found, followed by a table summarizing each file.
+///
+/// Returns whether every assertion in every file passed.
+pub fn run(options: &Options, colored: bool, colored_errors: bool) -> bool {
+    let paint = |style: &str, text: &str| {
+        if colored {
+            format!("\x1b[{style}m{text}\x1b[00m")
+        } else {
+            text.to_owned()
+        }
+    };
+
+    let mut results: Vec<FileResult> = vec![];
+    let mut record = |result: FileResult| {
+        for failure in result.report.failures.iter().chain(&result.error) {
+            eprintln!("{}\n", failure.render(colored_errors));
+        }
+        results.push(result);
+    };
+
+    let paths = options.sources.iter().filter_map(|source| match source {
+        Source::File(path) => Some(path),
+        Source::Eval(_) | Source::Stdin => None,
+    });
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            record(run_file(path, options));
+            continue;
+        }
+
+        let mut scripts = vec![];
+        if let Err(e) = collect_scripts(Path::new(path), &mut scripts) {
+            record(FileResult {
+                path: path.clone(),
+                report: TestReport::default(),
+                error: Some(e),
+            });
+            continue;
+        }
+        for script in scripts {
+            record(run_file(&script.to_string_lossy(), options));
+        }
+    }
+
+    let width = results
+        .iter()
+        .map(|result| result.path.chars().count())
+        .chain(["file".len()])
+        .max()
+        .unwrap_or_default();
+    println!("{:<width$}  passed  failed  status", "file");
+    for result in &results {
+        let status = result.status();
+        let status = if result.succeeded() {
+            paint("32", status) // green
+        } else {
+            paint("1;31", status) // bold red
+        };
+        println!(
+            "{:<width$}  {:>6}  {:>6}  {status}",
+            result.path,
+            result.report.passed,
+            result.report.failures.len()
+        );
+    }
+
+    let passed: usize = results.iter().map(|result| result.report.passed).sum();
+    let failed: usize = results
+        .iter()
+        .map(|result| result.report.failures.len())
+        .sum();
+    let errored = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let failed_files = results.iter().filter(|result| !result.succeeded()).count();
+    println!(
+        "\n{} files: {} ok, {failed_files} not ok ({errored} stopped by an error); {passed} assertions passed, {failed} failed",
+        results.len(),
+        results.len() - failed_files,
+    );
+
+    failed_files == 0
+}
@@ -0,0 +1,332 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, Zero};
+
+use crate::error::{Error, ErrorKind};
+use crate::print::format_fraction;
+use crate::quadratic::QuadraticIrrational;
+
+/// The value of an expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Rational(BigRational),
+
+    /// Arises from a periodic continued fraction such as `[1; @rep{1, 2}]`
+    Quadratic(QuadraticIrrational),
+}
+
+impl From<BigRational> for Value {
+    fn from(r: BigRational) -> Self {
+        Value::Rational(r)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Rational(r) => write!(f, "{r}"),
+            Value::Quadratic(q) => write!(f, "{q}"),
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Rational(r) => Value::Rational(-r),
+            Value::Quadratic(q) => QuadraticIrrational::new_value(
+                -q.rational_part(),
+                -q.irrational_coefficient(),
+                q.radicand().clone(),
+            ),
+        }
+    }
+}
+
+/// The `n`-th root of `x` if it is a rational
+fn nth_root_exact(x: &BigRational, n: u32) -> Option<BigRational> {
+    if x.is_negative() && n.is_multiple_of(2) {
+        return None;
+    }
+    let numer = x.numer().nth_root(n);
+    let denom = x.denom().nth_root(n);
+    if numer.pow(n) == *x.numer() && denom.pow(n) == *x.denom() {
+        Some(BigRational::new(numer, denom))
+    } else {
+        None
+    }
+}
+
+/// The most bits that a power such as `2^4000000000` may take, roughly, before it is refused instead of computed
+const MAX_POWER_BITS: u64 = 1 << 18;
+
+/// `(r, s, d)` such that the value equals `r + s√d`; `d` is `None` for a rational
+type Parts<'a> = (BigRational, BigRational, Option<&'a BigInt>);
+
+impl Value {
+    #[must_use]
+    pub fn zero() -> Self {
+        Value::Rational(BigRational::zero())
+    }
+
+    /// 1 for true and 0 for false, as given by the comparisons
+    #[must_use]
+    pub fn from_bool(b: bool) -> Self {
+        Value::Rational(if b {
+            BigRational::one()
+        } else {
+            BigRational::zero()
+        })
+    }
+
+    /// Whether the value counts as true in a condition, i.e. is not zero
+    #[must_use]
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Rational(r) => !r.is_zero(),
+            // a quadratic irrational is never zero
+            Value::Quadratic(_) => true,
+        }
+    }
+
+    fn parts(&self) -> Parts<'_> {
+        match self {
+            Value::Rational(r) => (r.clone(), BigRational::zero(), None),
+            Value::Quadratic(q) => (
+                q.rational_part().clone(),
+                q.irrational_coefficient().clone(),
+                Some(q.radicand()),
+            ),
+        }
+    }
+
+    /// Brings both operands into the same field Q(√d)
+    fn common_parts<'a>(&'a self, rhs: &'a Value) -> Result<(Parts<'a>, Parts<'a>), Error> {
+        let (lhs, rhs) = (self.parts(), rhs.parts());
+        match (lhs.2, rhs.2) {
+            (Some(d1), Some(d2)) if d1 != d2 => Err(Error::new(
+                ErrorKind::Arithmetic,
+                format!("Cannot exactly combine a number involving √{d1} with one involving √{d2}"),
+            )),
+            (d1, d2) => {
+                let d = d1.or(d2);
+                Ok(((lhs.0, lhs.1, d), (rhs.0, rhs.1, d)))
+            }
+        }
+    }
+
+    fn from_parts((r, s, d): Parts<'_>) -> Value {
+        match d {
+            Some(d) => QuadraticIrrational::new_value(r, s, d.clone()),
+            None => Value::Rational(r),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Fails when the operands involve distinct square roots, such as `√2 + √3`,
+    /// whose sum is not a quadratic irrational.
+    pub fn add(&self, rhs: &Value) -> Result<Value, Error> {
+        let ((r1, s1, d), (r2, s2, _)) = self.common_parts(rhs)?;
+        Ok(Self::from_parts((r1 + r2, s1 + s2, d)))
+    }
+
+    /// # Errors
+    ///
+    /// Fails under the same condition as [`Value::add`].
+    pub fn sub(&self, rhs: &Value) -> Result<Value, Error> {
+        self.add(&-rhs.clone())
+    }
+
+    /// # Errors
+    ///
+    /// Fails under the same condition as [`Value::add`].
+    pub fn mul(&self, rhs: &Value) -> Result<Value, Error> {
+        let ((r1, s1, d), (r2, s2, _)) = self.common_parts(rhs)?;
+        let dd = BigRational::from_integer(d.cloned().unwrap_or_default());
+        Ok(Self::from_parts((
+            &r1 * &r2 + &s1 * &s2 * dd,
+            r1 * s2 + s1 * r2,
+            d,
+        )))
+    }
+
+    /// # Errors
+    ///
+    /// Fails on division by zero, and under the same condition as [`Value::add`].
+    pub fn div(&self, rhs: &Value) -> Result<Value, Error> {
+        self.mul(&rhs.recip()?)
+    }
+
+    /// Raises to an integer power, or to a fractional power `p/q` when the `q`-th root is an exact rational
+    ///
+    /// # Errors
+    ///
+    /// Fails when the result is not exact, when the exponent is too large, or when zero is raised to a negative power.
+    pub fn pow(&self, exponent: &Value) -> Result<Value, Error> {
+        let Value::Rational(exponent) = exponent else {
+            return Err(Error::new(
+                ErrorKind::Arithmetic,
+                format!("The exponent must be rational, but found {exponent}"),
+            ));
+        };
+
+        if exponent.is_integer() {
+            return self.pow_integer(exponent.numer());
+        }
+
+        let Value::Rational(base) = self else {
+            return Err(Error::new(
+                ErrorKind::Arithmetic,
+                format!("A quadratic irrational {self} cannot be raised to a non-integer power {exponent}"),
+            ));
+        };
+        let root_index = u32::try_from(exponent.denom()).map_err(|_| {
+            Error::new(
+                ErrorKind::Arithmetic,
+                format!("The exponent {exponent} has too large a denominator"),
+            )
+        })?;
+        let root = nth_root_exact(base, root_index).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Arithmetic,
+                format!("({base})^({exponent}) is not a rational number"),
+            )
+        })?;
+        Value::Rational(root).pow_integer(exponent.numer())
+    }
+
+    /// Roughly how many bits each factor adds to a power of `self`, which is 0 only for 0, 1 and -1
+    fn bits_per_factor(&self) -> u64 {
+        let (r, s, d) = self.parts();
+        [r.numer(), r.denom(), s.numer(), s.denom()]
+            .into_iter()
+            .chain(d)
+            .map(BigInt::bits)
+            .max()
+            .unwrap_or(0)
+            .saturating_sub(1)
+    }
+
+    fn pow_integer(&self, exponent: &BigInt) -> Result<Value, Error> {
+        let bits_per_factor = self.bits_per_factor();
+        let magnitude = if bits_per_factor == 0 {
+            // the powers of 0, 1 and -1 only depend on whether the exponent is zero or odd
+            match exponent {
+                e if e.is_zero() => 0,
+                e if e.is_odd() => 1,
+                _ => 2,
+            }
+        } else if BigInt::from(bits_per_factor) * exponent.abs() > BigInt::from(MAX_POWER_BITS) {
+            return Err(Error::new(
+                ErrorKind::Arithmetic,
+                format!("The exponent {exponent} is too large: the result would exceed {MAX_POWER_BITS} bits"),
+            ));
+        } else {
+            // bounded by MAX_POWER_BITS
+            u64::try_from(exponent.abs()).unwrap_or(u64::MAX)
+        };
+
+        // binary exponentiation
+        let mut result = Value::Rational(BigRational::one());
+        let mut square = self.clone();
+        let mut rest = magnitude;
+        while rest > 0 {
+            if rest % 2 == 1 {
+                result = result.mul(&square)?;
+            }
+            rest /= 2;
+            if rest > 0 {
+                square = square.mul(&square)?;
+            }
+        }
+
+        if exponent.is_negative() {
+            if self == &Value::zero() {
+                return Err(Error::new(
+                    ErrorKind::DivisionByZero,
+                    "Zero cannot be raised to a negative power",
+                ));
+            }
+            result.recip()
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Fails when `self` is zero.
+    pub fn recip(&self) -> Result<Value, Error> {
+        match self {
+            Value::Rational(r) if r.is_zero() => {
+                Err(Error::new(ErrorKind::DivisionByZero, "Division by zero"))
+            }
+            Value::Rational(r) => Ok(Value::Rational(r.recip())),
+            Value::Quadratic(q) => {
+                // 1 / (r + s√d) == (r - s√d) / (r^2 - s^2 d), whose denominator never vanishes
+                let (r, s) = (q.rational_part(), q.irrational_coefficient());
+                let norm = r * r - s * s * BigRational::from_integer(q.radicand().clone());
+                Ok(QuadraticIrrational::new_value(
+                    r / &norm,
+                    -s / norm,
+                    q.radicand().clone(),
+                ))
+            }
+        }
+    }
+
+    /// The sign of the value, exact even for a quadratic irrational
+    #[must_use]
+    pub fn signum(&self) -> Ordering {
+        match self {
+            Value::Rational(r) => r.cmp(&BigRational::zero()),
+            Value::Quadratic(q) => {
+                // r + s√d has the sign of whichever of r and s√d is larger in magnitude
+                let (r, s) = (q.rational_part(), q.irrational_coefficient());
+                let zero = BigRational::zero();
+                let s_sign = s.cmp(&zero);
+                if r.cmp(&zero) == s_sign
+                    || r * r < s * s * BigRational::from_integer(q.radicand().clone())
+                {
+                    s_sign
+                } else {
+                    r.cmp(&zero)
+                }
+            }
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Fails under the same condition as [`Value::add`].
+    pub fn compare(&self, rhs: &Value) -> Result<Ordering, Error> {
+        Ok(self.sub(rhs)?.signum())
+    }
+
+    #[must_use]
+    pub fn abs(&self) -> Value {
+        if self.signum() == Ordering::Less {
+            -self.clone()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// The fraction such as `-7/3`, or the closed form of a quadratic irrational such as `(1 + √5)/2`,
+    /// with each integer written in the given radix
+    #[must_use]
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        match self {
+            Value::Rational(r) => format_fraction(r, radix),
+            Value::Quadratic(q) => q.to_str_radix(radix),
+        }
+    }
+}